@@ -0,0 +1,290 @@
+// The segmentation half of the Unicode Bidirectional Algorithm (UAX #9): splitting text into
+// paragraphs, finding each paragraph's direction, and splitting a paragraph into runs that go the
+// same way. Reordering the runs for display is left to whoever draws them.
+
+use crate::ucd::{bidi_class, BidiClass};
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Ltr,
+    Rtl,
+}
+
+// A run of text at the same embedding level. Even levels are left to right, and odd levels right to
+// left, so e.g. a number in Hebrew text gets level 2, inside the Hebrew's level 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BidiRun {
+    pub range: Range<usize>,
+    pub level: u8,
+}
+
+impl BidiRun {
+    pub fn direction(&self) -> Direction {
+        if self.level.is_multiple_of(2) {
+            Direction::Ltr
+        } else {
+            Direction::Rtl
+        }
+    }
+}
+
+// P1: each paragraph ends after a paragraph separator (Bidi_Class B), which stays with the
+// paragraph before it. CR LF counts as one separator.
+pub fn bidi_paragraphs(code_points: &[u32]) -> Vec<Range<usize>> {
+    let mut paragraphs = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+    while pos < code_points.len() {
+        if bidi_class(code_points[pos]) == BidiClass::B {
+            if code_points[pos] == 0x0D && code_points.get(pos + 1) == Some(&0x0A) {
+                pos += 1;
+            }
+            paragraphs.push(start..pos + 1);
+            start = pos + 1;
+        }
+        pos += 1;
+    }
+    if start < code_points.len() {
+        paragraphs.push(start..code_points.len());
+    }
+    paragraphs
+}
+
+// P2, P3: the direction of the first strong letter, skipping over anything between an isolate
+// initiator and its matching PDI. None if there isn't one, in which case it's up to the caller,
+// but it's usually left to right.
+pub fn paragraph_direction(paragraph: &[u32]) -> Option<Direction> {
+    use BidiClass::*;
+    let mut isolates = 0;
+    for cp in paragraph {
+        match bidi_class(*cp) {
+            LRI | RLI | FSI => isolates += 1,
+            PDI if isolates > 0 => isolates -= 1,
+            L if isolates == 0 => return Some(Direction::Ltr),
+            R | AL if isolates == 0 => return Some(Direction::Rtl),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Splits a paragraph into runs by resolving the implicit levels: the weak types (W1–W7), the
+// neutrals (N1, N2), and then the levels (I1, I2). The explicit embeddings, overrides and isolates
+// (X1–X10) and the bracket pairs (N0) aren't handled, so their controls are treated as neutrals,
+// and all of the text is at the base level to start with.
+pub fn bidi_runs(paragraph: &[u32], base: Direction) -> Vec<BidiRun> {
+    use BidiClass::*;
+    let base_level = match base {
+        Direction::Ltr => 0,
+        Direction::Rtl => 1,
+    };
+    let sos = match base {
+        Direction::Ltr => L,
+        Direction::Rtl => R,
+    };
+    let mut types: Vec<BidiClass> = paragraph
+        .iter()
+        .map(|cp| match bidi_class(*cp) {
+            LRE | RLE | LRO | RLO | PDF | BN | LRI | RLI | FSI | PDI => ON,
+            bc => bc,
+        })
+        .collect();
+
+    // W1: marks take the type of what they're on.
+    for i in 0..types.len() {
+        if types[i] == NSM {
+            types[i] = if i == 0 { sos } else { types[i - 1] };
+        }
+    }
+    // W2: European numbers after Arabic letters are Arabic numbers. W3: Arabic letters are R.
+    let mut last_strong = sos;
+    for t in types.iter_mut() {
+        match *t {
+            L | R | AL => last_strong = *t,
+            EN if last_strong == AL => *t = AN,
+            _ => {}
+        }
+    }
+    for t in types.iter_mut() {
+        if *t == AL {
+            *t = R;
+        }
+    }
+    // W4: a single separator between two numbers of the same kind joins them.
+    for i in 1..types.len().saturating_sub(1) {
+        let (prev, next) = (types[i - 1], types[i + 1]);
+        if prev == next
+            && (prev == EN && matches!(types[i], ES | CS) || prev == AN && types[i] == CS)
+        {
+            types[i] = prev;
+        }
+    }
+    // W5: terminators next to European numbers, like the % in 50%, are part of the number.
+    let mut i = 0;
+    while i < types.len() {
+        if types[i] != ET {
+            i += 1;
+            continue;
+        }
+        let end = i + types[i..].iter().take_while(|t| **t == ET).count();
+        if (i > 0 && types[i - 1] == EN) || types.get(end) == Some(&EN) {
+            types[i..end].fill(EN);
+        }
+        i = end;
+    }
+    // W6: any other separators and terminators are neutral.
+    for t in types.iter_mut() {
+        if matches!(*t, ES | ET | CS) {
+            *t = ON;
+        }
+    }
+    // W7: European numbers in left to right text are L.
+    let mut last_strong = sos;
+    for t in types.iter_mut() {
+        match *t {
+            L | R => last_strong = *t,
+            EN if last_strong == L => *t = L,
+            _ => {}
+        }
+    }
+    // N1: neutrals between two strong types that go the same way go that way too, with numbers
+    // counting as R. N2: the rest go the base direction.
+    let strong = |t: BidiClass| match t {
+        L => Some(L),
+        R | EN | AN => Some(R),
+        _ => None,
+    };
+    let mut i = 0;
+    while i < types.len() {
+        if strong(types[i]).is_some() {
+            i += 1;
+            continue;
+        }
+        let end = i + types[i..]
+            .iter()
+            .take_while(|t| strong(**t).is_none())
+            .count();
+        let before = if i == 0 {
+            sos
+        } else {
+            strong(types[i - 1]).unwrap()
+        };
+        let after = types.get(end).map_or(sos, |t| strong(*t).unwrap());
+        types[i..end].fill(if before == after { before } else { sos });
+        i = end;
+    }
+    // I1, I2.
+    let levels = types.iter().map(|t| match (base_level, t) {
+        (0, R) => 1,
+        (0, EN | AN) => 2,
+        (0, _) => 0,
+        (_, L | EN | AN) => 2,
+        _ => 1,
+    });
+
+    let mut runs: Vec<BidiRun> = Vec::new();
+    for (i, level) in levels.enumerate() {
+        match runs.last_mut() {
+            Some(run) if run.level == level => run.range.end = i + 1,
+            _ => runs.push(BidiRun {
+                range: i..i + 1,
+                level,
+            }),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chars::{from_chars, to_string};
+
+    fn runs(s: &str, base: Direction) -> Vec<(String, u8)> {
+        let code_points = from_chars(s.chars());
+        bidi_runs(&code_points, base)
+            .into_iter()
+            .map(|run| {
+                let text = to_string(&code_points[run.range.clone()]).unwrap();
+                (text, run.level)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bidi_paragraphs() {
+        assert_eq!(
+            bidi_paragraphs(&from_chars("one\r\ntwo\nthree\u{2029}".chars())),
+            [0..5, 5..9, 9..15]
+        );
+        assert_eq!(
+            bidi_paragraphs(&from_chars("\n\nx".chars())),
+            [0..1, 1..2, 2..3]
+        );
+        assert!(bidi_paragraphs(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_paragraph_direction() {
+        assert_eq!(
+            paragraph_direction(&from_chars("123 abc".chars())),
+            Some(Direction::Ltr)
+        );
+        assert_eq!(
+            paragraph_direction(&from_chars("\"שלום\" he said".chars())),
+            Some(Direction::Rtl)
+        );
+        assert_eq!(
+            paragraph_direction(&from_chars("مرحبا".chars())),
+            Some(Direction::Rtl)
+        );
+        // The Hebrew is in an isolate, so it doesn't count.
+        assert_eq!(
+            paragraph_direction(&from_chars("\u{2067}שלום\u{2069} hi".chars())),
+            Some(Direction::Ltr)
+        );
+        assert_eq!(paragraph_direction(&from_chars("123 !".chars())), None);
+    }
+
+    #[test]
+    fn test_bidi_runs() {
+        use Direction::*;
+        assert_eq!(
+            runs("abc אבג def", Ltr),
+            [
+                ("abc ".to_string(), 0),
+                ("אבג".to_string(), 1),
+                (" def".to_string(), 0)
+            ]
+        );
+        // The space is between two R-ish things, so it goes with them.
+        assert_eq!(
+            runs("אבג 123", Rtl),
+            [("אבג ".to_string(), 1), ("123".to_string(), 2)]
+        );
+        // Numbers after Arabic letters are Arabic numbers, even in left to right text. The
+        // separator stays with them, but terminators only go with European numbers.
+        assert_eq!(
+            runs("ابت 1,000%", Ltr),
+            [
+                ("ابت ".to_string(), 1),
+                ("1,000".to_string(), 2),
+                ("%".to_string(), 0)
+            ]
+        );
+        assert_eq!(runs("price: $5", Ltr), [("price: $5".to_string(), 0)]);
+        assert_eq!(runs("abc", Rtl), [("abc".to_string(), 2)]);
+        // Marks go with their letter.
+        assert_eq!(
+            runs("a\u{5B4}\u{5D0}\u{5B4}", Ltr),
+            [
+                ("a\u{5B4}".to_string(), 0),
+                ("\u{5D0}\u{5B4}".to_string(), 1)
+            ]
+        );
+        assert!(runs("", Ltr).is_empty());
+        assert_eq!(bidi_runs(&from_chars("x".chars()), Ltr)[0].direction(), Ltr);
+        assert_eq!(bidi_runs(&from_chars("א".chars()), Ltr)[0].direction(), Rtl);
+    }
+}
@@ -1,42 +1,169 @@
 // These are the default casing operations, but locale-specific ‘tailored’ casings are possible.
 
-use crate::normalise::{decompose, to_nfd};
-use crate::ucd::{case_folding, case_ignorable, cased, lowercase_mapping, uppercase_mapping};
+use crate::cp_iter::{CodePointIter, LossyCodePointIter};
+use crate::helpers::{encode_utf8_into, fnv1a};
+use crate::normalise::{decompose, to_nfc, to_nfd, to_nfkd, NormaliseIter};
+use crate::segmentation::{SentenceIter, WordIter};
+use crate::ucd::{
+    case_folding, case_folding_sources, case_ignorable, cased, combining_class,
+    contains_ypogegrammeni, lowercase_mapping, nfkc_casefold, soft_dotted, special_casing,
+    titlecase_mapping, uppercase_mapping, CasingContext, SpecialCasing,
+};
+use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::ops::Range;
 
-// There are a couple documented cases this doesn't handle.
-// 1. In Lithuanian small i with an accent still has a dot, which needs to be added back as
-//    the codepoint 0307 (combining dot)
-// 2. In Turkish the letter 0049 ‘I’ lowercases to 0131 "ı" (small dotless i).
-// They're described in SpecialCasing.txt, but can possibly be ignored, since they depend on the
-// locale, at which point, it should probably be combined with the CLDR and the whole thing made
-// locale-aware.
-pub fn to_lowercase(code_points: Vec<u32>) -> Vec<u32> {
-    let mut pos = 0;
-    let len = code_points.len();
-    let mut out = Vec::with_capacity(len);
-    while pos < len {
-        let code_point = code_points[pos];
-        match code_point {
-            0x0130 => out.extend([105, 775]),
-            0x03A3 => out.push(if is_final_sigma(&code_points, pos) {
-                0x03C2
-            } else {
-                0x03C3
-            }),
-            _ => out.push(lowercase_mapping(code_point).unwrap_or(code_point)),
-        }
-        pos += 1;
+// The languages that have their own rules in SpecialCasing.txt. Everything else uses the root
+// casing. In Turkish and Azerbaijani, I and i are different letters to ı and İ, so I lowercases to
+// ı, and i uppercases to İ. In Lithuanian, i keeps its dot when it gets an accent above it, so it
+// has to be added back as U+0307 when lowercasing, and taken away again when uppercasing.
+// Greek and Dutch aren't in SpecialCasing.txt, but get their own rules here too. In Greek, the
+// accents are dropped when a word is put in capitals, see to_greek_uppercase. In Dutch, IJ is
+// treated as a single letter, so both halves get capitalised at the start of a word when
+// titlecasing, e.g. ijsland → IJsland.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaseLocale {
+    Root,
+    Azerbaijani,
+    Dutch,
+    Greek,
+    Lithuanian,
+    Turkish,
+}
+
+impl CaseLocale {
+    // The language tag that SpecialCasing.txt uses for it.
+    fn language(self) -> Option<&'static str> {
+        match self {
+            CaseLocale::Root => None,
+            CaseLocale::Azerbaijani => Some("az"),
+            CaseLocale::Dutch => Some("nl"),
+            CaseLocale::Greek => Some("el"),
+            CaseLocale::Lithuanian => Some("lt"),
+            CaseLocale::Turkish => Some("tr"),
+        }
+    }
+}
+
+const COMBINING_DOT_ABOVE: u32 = 0x0307;
+
+// How to decide whether a sigma is at the end of a word. The standard way is the Final_Sigma
+// condition, which only looks at whether the letters either side of it are cased, skipping over
+// case-ignorables. That gets some things wrong, e.g. a sigma followed by a digit is final, even
+// though the digit is part of the same word, and one followed by ^ and a letter isn't, even though
+// ^ ends the word. The alternative is to use the actual word boundaries, so it's final if there's
+// a cased letter before it in its word, and nothing after it but case-ignorables, like accents.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SigmaMode {
+    Standard,
+    WordBoundary,
+}
+
+pub fn to_lowercase(code_points: Vec<u32>, locale: CaseLocale) -> Vec<u32> {
+    to_lowercase_with_sigma(code_points, locale, SigmaMode::Standard)
+}
+
+pub fn to_lowercase_with_sigma(
+    code_points: Vec<u32>,
+    locale: CaseLocale,
+    sigma: SigmaMode,
+) -> Vec<u32> {
+    let mut out = Vec::with_capacity(code_points.len());
+    for pos in 0..code_points.len() {
+        push_lowercase(&code_points, pos, locale, sigma, &mut out);
     }
     out
 }
 
+// Lowercasing a single code point, but it needs the rest of the string, for the sigma and the
+// locale-specific rules.
+fn push_lowercase(
+    code_points: &[u32],
+    pos: usize,
+    locale: CaseLocale,
+    sigma: SigmaMode,
+    out: &mut Vec<u32>,
+) {
+    let code_point = code_points[pos];
+    match special_mapping(code_points, pos, locale, sigma) {
+        Some(special) => out.extend_from_slice(&special.lower),
+        None => out.extend(lowercase_mapping(code_point).unwrap_or(vec![code_point])),
+    }
+}
+
+// The first conditional mapping from SpecialCasing.txt that applies, if any. It has to be for the
+// locale's language, or for no language at all, and its context has to hold at pos.
+fn special_mapping(
+    code_points: &[u32],
+    pos: usize,
+    locale: CaseLocale,
+    sigma: SigmaMode,
+) -> Option<&'static SpecialCasing> {
+    special_casing(code_points[pos]).iter().find(|special| {
+        let language_matches =
+            special.language.is_none() || special.language.as_deref() == locale.language();
+        language_matches
+            && special.context.is_none_or(|context| {
+                is_in_context(code_points, pos, context, sigma) != special.negated
+            })
+    })
+}
+
+fn is_in_context(
+    code_points: &[u32],
+    pos: usize,
+    context: CasingContext,
+    sigma: SigmaMode,
+) -> bool {
+    match context {
+        CasingContext::FinalSigma => match sigma {
+            SigmaMode::Standard => is_final_sigma(code_points, pos),
+            SigmaMode::WordBoundary => is_final_in_word(code_points, pos),
+        },
+        CasingContext::AfterSoftDotted => is_after(code_points, pos, soft_dotted),
+        CasingContext::MoreAbove => is_more_above(code_points, pos),
+        CasingContext::BeforeDot => is_before_dot(code_points, pos),
+        CasingContext::AfterI => is_after(code_points, pos, |cp| cp == 0x0049),
+    }
+}
+
+// The conditions from SpecialCasing.txt (Table 3-17). They look past any combining marks that
+// aren't above the letter, but stop at a starter, or another mark above it.
+fn is_blocking(code_point: u32) -> bool {
+    matches!(combining_class(code_point), 0 | 230)
+}
+
+// C is followed by a character of combining class 230 (Above) with no intervening character of
+// combining class 0 or 230 (Above).
+fn is_more_above(code_points: &[u32], pos: usize) -> bool {
+    code_points[pos + 1..]
+        .iter()
+        .find(|cp| is_blocking(**cp))
+        .is_some_and(|cp| combining_class(*cp) == 230)
+}
+
+// C is followed by U+0307, with no intervening character of combining class 0 or 230.
+fn is_before_dot(code_points: &[u32], pos: usize) -> bool {
+    code_points[pos + 1..].iter().find(|cp| is_blocking(**cp)) == Some(&COMBINING_DOT_ABOVE)
+}
+
+// There is a character matching the predicate before C, with no intervening character of
+// combining class 0 or 230. This is After_I and After_Soft_Dotted.
+fn is_after(code_points: &[u32], pos: usize, matches: impl Fn(u32) -> bool) -> bool {
+    code_points[..pos]
+        .iter()
+        .rev()
+        .find(|cp| is_blocking(**cp))
+        .is_some_and(|cp| matches(*cp))
+}
+
 // 03A3 GREEK CAPITAL LETTER SIGMA has a different lowercase if it occurs at the end
 // of a word. The way this is checked is (Table 3-17):
 // > C is preceded by a sequence consisting of a cased letter and then zero or
 // > more case-ignorable characters, and C is not followed by a sequence consisting
 // > of zero or more case-ignorable characters and then a cased letter.
-fn is_final_sigma(code_points: &Vec<u32>, sigma_pos: usize) -> bool {
+fn is_final_sigma(code_points: &[u32], sigma_pos: usize) -> bool {
     let len = code_points.len();
     let prev_char_cased = {
         let mut xs = code_points
@@ -62,15 +189,338 @@ fn is_final_sigma(code_points: &Vec<u32>, sigma_pos: usize) -> bool {
     }
 }
 
+fn is_final_in_word(code_points: &[u32], sigma_pos: usize) -> bool {
+    let mut start = 0;
+    for word in WordIter::new(code_points) {
+        let end = start + word.len();
+        if sigma_pos < end {
+            return code_points[start..sigma_pos].iter().any(|cp| cased(*cp))
+                && code_points[sigma_pos + 1..end]
+                    .iter()
+                    .all(|cp| case_ignorable(*cp));
+        }
+        start = end;
+    }
+    false
+}
+
 // I'm not aware of any edge cases for upper-casing, at least none that aren't locale-specific.
-pub fn to_uppercase(code_points: Vec<u32>) -> Vec<u32> {
+pub fn to_uppercase(code_points: Vec<u32>, locale: CaseLocale) -> Vec<u32> {
+    if locale == CaseLocale::Greek {
+        return to_greek_uppercase(&code_points);
+    }
     let mut out = Vec::with_capacity(code_points.len());
-    for code_point in code_points {
-        out.extend(uppercase_mapping(code_point).unwrap_or(vec![code_point]));
+    for pos in 0..code_points.len() {
+        push_uppercase(&code_points, pos, locale, &mut out);
+    }
+    out
+}
+
+fn push_uppercase(code_points: &[u32], pos: usize, locale: CaseLocale, out: &mut Vec<u32>) {
+    let code_point = code_points[pos];
+    match special_mapping(code_points, pos, locale, SigmaMode::Standard) {
+        Some(special) => out.extend_from_slice(&special.upper),
+        None => out.extend(uppercase_mapping(code_point).unwrap_or(vec![code_point])),
+    }
+}
+
+// The accents that Greek drops in capitals: varia, tonos, psili, dasia, koronis and perispomeni.
+// The dialytika (diaeresis) stays, because it changes the pronunciation.
+const GREEK_ACCENTS: &[u32] = &[0x0300, 0x0301, 0x0313, 0x0314, 0x0342, 0x0343];
+
+// Uppercasing the way ICU does for el. Greek text in capitals is written without the accents and
+// breathings, but the plain mappings keep them, e.g. ά → Ά. So it works on the decomposed letters,
+// and leaves the accents off. That loses some information, because an accent on the first of two
+// vowels shows they aren't a diphthong, e.g. in άι. So in that case the second vowel gets a
+// dialytika instead: ΑΪ. The exception is ή on its own, which is a word (or), and keeps its accent
+// to tell it apart from the article η. The result is in NFC.
+fn to_greek_uppercase(code_points: &[u32]) -> Vec<u32> {
+    let nfd = to_nfd(code_points);
+    let mut letters: Vec<&[u32]> = Vec::new();
+    let mut start = 0;
+    for pos in 1..=nfd.len() {
+        if pos == nfd.len() || combining_class(nfd[pos]) == 0 {
+            letters.push(&nfd[start..pos]);
+            start = pos;
+        }
+    }
+
+    let mut out = Vec::with_capacity(nfd.len());
+    // The previous letter, lowercased, if it was a Greek vowel that had an accent.
+    let mut accented_vowel = None;
+    for (i, letter) in letters.iter().enumerate() {
+        let base = letter[0];
+        let upper = |cp: u32| uppercase_mapping(cp).unwrap_or(vec![cp]);
+        if !is_greek_letter(base) {
+            letter.iter().for_each(|cp| out.extend(upper(*cp)));
+            accented_vowel = None;
+            continue;
+        }
+        let lower = lowercase_mapping(base).map_or(base, |lower| lower[0]);
+        let marks = &letter[1..];
+        let accented = marks.iter().any(|cp| GREEK_ACCENTS.contains(cp));
+        let is_alone = |letter: Option<&&[u32]>| letter.is_none_or(|l| !cased(l[0]));
+        let disjunctive_eta = lower == 0x03B7
+            && marks == [0x0301]
+            && is_alone(i.checked_sub(1).and_then(|j| letters.get(j)))
+            && is_alone(letters.get(i + 1));
+
+        out.extend(upper(base));
+        let breaks_diphthong = matches!(
+            (accented_vowel, lower),
+            (Some(0x03B1 | 0x03B5 | 0x03BF | 0x03C5), 0x03B9)
+                | (Some(0x03B1 | 0x03B5 | 0x03B7 | 0x03BF), 0x03C5)
+        );
+        if breaks_diphthong && !marks.contains(&0x0308) {
+            out.push(0x0308);
+        }
+        for mark in marks {
+            if disjunctive_eta || !GREEK_ACCENTS.contains(mark) {
+                out.extend(upper(*mark));
+            }
+        }
+        accented_vowel = if accented { Some(lower) } else { None };
+    }
+    to_nfc(&out)
+}
+
+fn is_greek_letter(code_point: u32) -> bool {
+    matches!(code_point, 0x0386..=0x03FF | 0x1F00..=0x1FFF) && cased(code_point)
+}
+
+// Like Python's str.swapcase, except that titlecase letters like ǅ get lowercased rather than left
+// alone. Anything with a lowercase mapping is uppercase or titlecase, so it gets lowercased (with
+// the sigma rule), and anything else with an uppercase mapping is lowercase.
+// Applying it twice doesn't necessarily get the original back, e.g. ß → SS → ss.
+pub fn swapcase(code_points: Vec<u32>) -> Vec<u32> {
+    let mut out = Vec::with_capacity(code_points.len());
+    for (pos, code_point) in code_points.iter().enumerate() {
+        if lowercase_mapping(*code_point).is_some() {
+            push_lowercase(
+                &code_points,
+                pos,
+                CaseLocale::Root,
+                SigmaMode::Standard,
+                &mut out,
+            );
+        } else {
+            out.extend(uppercase_mapping(*code_point).unwrap_or(vec![*code_point]));
+        }
+    }
+    out
+}
+
+// > toTitlecase(X): Find the word boundaries in X according to Unicode Standard Annex #29.
+// > For each word boundary, find the first cased character F following the word boundary. If F
+// > exists, map F to Titlecase_Mapping(F); then map all characters C between F and the following
+// > word boundary to Lowercase_Mapping(C).
+// Anything before F is left as it is, so "'hello" becomes "'Hello".
+pub fn to_titlecase(code_points: Vec<u32>, locale: CaseLocale) -> Vec<u32> {
+    let mut out = Vec::with_capacity(code_points.len());
+    let mut pos = 0;
+    for word in WordIter::new(&code_points) {
+        match word.iter().position(|cp| cased(*cp)) {
+            None => out.extend_from_slice(word),
+            Some(first) => {
+                out.extend_from_slice(&word[..first]);
+                let f = word[first];
+                match special_mapping(&code_points, pos + first, locale, SigmaMode::Standard) {
+                    Some(special) => out.extend_from_slice(&special.title),
+                    None => out.extend(titlecase_mapping(f).unwrap_or(vec![f])),
+                }
+                let mut rest = pos + first + 1;
+                if locale == CaseLocale::Dutch
+                    && matches!(f, 0x0049 | 0x0069)
+                    && matches!(word.get(first + 1), Some(0x004A | 0x006A))
+                {
+                    out.push(0x004A);
+                    rest += 1;
+                }
+                for i in rest..(pos + word.len()) {
+                    push_lowercase(&code_points, i, locale, SigmaMode::Standard, &mut out);
+                }
+            }
+        }
+        pos += word.len();
+    }
+    out
+}
+
+// Capitalises the first cased letter of each sentence, as a text cleanup. Unlike to_titlecase,
+// the rest is left as it is, so names and acronyms keep their capitals. The sentences are the
+// UAX #29 ones, which don't end at a full stop followed by a lowercase word, since it could be an
+// abbreviation, e.g. "etc. and so on", so only ! and ? reliably start a new one in all-lowercase
+// text. Uses the titlecase mapping, since that's what's wanted at the start of a word,
+// e.g. ǆ → ǅ rather than Ǆ.
+pub fn titlecase_sentences(s: &str) -> String {
+    let code_points = to_code_points(s);
+    let mut out = Vec::with_capacity(code_points.len());
+    let mut pos = 0;
+    for sentence in SentenceIter::new(&code_points) {
+        match sentence.iter().position(|cp| cased(*cp)) {
+            None => out.extend_from_slice(sentence),
+            Some(first) => {
+                out.extend_from_slice(&sentence[..first]);
+                let f = sentence[first];
+                match special_mapping(
+                    &code_points,
+                    pos + first,
+                    CaseLocale::Root,
+                    SigmaMode::Standard,
+                ) {
+                    Some(special) => out.extend_from_slice(&special.title),
+                    None => out.extend(titlecase_mapping(f).unwrap_or(vec![f])),
+                }
+                out.extend_from_slice(&sentence[first + 1..]);
+            }
+        }
+        pos += sentence.len();
     }
+    from_code_points(out)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaseOp {
+    Lowercase,
+    Uppercase,
+    Fold,
+}
+
+// Changes the case of just part of the text, like an editor's "uppercase selection". Doing it to
+// the slice on its own would get the context wrong, e.g. a sigma at the end of the selection isn't
+// final if the word carries on after it. So the conditions are checked against the whole text,
+// and the rest of it is copied over as it is. Uses the root locale.
+pub fn case_map_range(code_points: &[u32], range: Range<usize>, op: CaseOp) -> Vec<u32> {
+    let mut out = Vec::with_capacity(code_points.len());
+    out.extend_from_slice(&code_points[..range.start]);
+    for pos in range.clone() {
+        match op {
+            CaseOp::Lowercase => push_lowercase(
+                code_points,
+                pos,
+                CaseLocale::Root,
+                SigmaMode::Standard,
+                &mut out,
+            ),
+            CaseOp::Uppercase => push_uppercase(code_points, pos, CaseLocale::Root, &mut out),
+            CaseOp::Fold => out.extend(fold_code_point(code_points[pos])),
+        }
+    }
+    out.extend_from_slice(&code_points[range.end..]);
     out
 }
 
+// String versions of the above, using the root locale.
+pub fn lowercase(s: &str) -> String {
+    from_code_points(to_lowercase(to_code_points(s), CaseLocale::Root))
+}
+
+pub fn uppercase(s: &str) -> String {
+    from_code_points(to_uppercase(to_code_points(s), CaseLocale::Root))
+}
+
+pub fn fold(s: &str) -> String {
+    from_code_points(case_fold(&to_code_points(s)))
+}
+
+// Versions that don't allocate a new string if nothing changes, for things like normalising
+// identifiers, where most of them will already be in the right case. They're Cow::Owned only if
+// the case actually changed, so that doubles as the changed flag. Most code points map to
+// themselves, which is just a failed lookup, so it only does the full mapping from the first one
+// that doesn't.
+pub fn to_lowercase_cow(code_points: &[u32], locale: CaseLocale) -> Cow<'_, [u32]> {
+    map_if_changed(
+        code_points,
+        |cp| lowercase_mapping(cp).is_some() || !special_casing(cp).is_empty(),
+        |cps, pos, out| push_lowercase(cps, pos, locale, SigmaMode::Standard, out),
+    )
+}
+
+pub fn to_uppercase_cow(code_points: &[u32], locale: CaseLocale) -> Cow<'_, [u32]> {
+    if locale == CaseLocale::Greek {
+        // Accented capitals change too, so there's no quick way to tell.
+        let upper = to_greek_uppercase(code_points);
+        return if upper == code_points {
+            Cow::Borrowed(code_points)
+        } else {
+            Cow::Owned(upper)
+        };
+    }
+    map_if_changed(
+        code_points,
+        |cp| uppercase_mapping(cp).is_some() || !special_casing(cp).is_empty(),
+        |cps, pos, out| push_uppercase(cps, pos, locale, out),
+    )
+}
+
+pub fn case_fold_cow(code_points: &[u32]) -> Cow<'_, [u32]> {
+    map_if_changed(
+        code_points,
+        |cp| case_folding(cp).is_some(),
+        |cps, pos, out| out.extend(fold_code_point(cps[pos])),
+    )
+}
+
+// The candidates are the code points that might not map to themselves. They still get checked,
+// because a conditional mapping might not apply, like the Turkish one for i when lowercasing.
+fn map_if_changed<'a>(
+    code_points: &'a [u32],
+    is_candidate: impl Fn(u32) -> bool,
+    push: impl Fn(&[u32], usize, &mut Vec<u32>),
+) -> Cow<'a, [u32]> {
+    let mut mapped = Vec::new();
+    let first_change = (0..code_points.len())
+        .filter(|pos| is_candidate(code_points[*pos]))
+        .find(|pos| {
+            mapped.clear();
+            push(code_points, *pos, &mut mapped);
+            mapped != [code_points[*pos]]
+        });
+    match first_change {
+        None => Cow::Borrowed(code_points),
+        Some(first) => {
+            let mut out = Vec::with_capacity(code_points.len());
+            out.extend_from_slice(&code_points[..first]);
+            out.append(&mut mapped);
+            for pos in first + 1..code_points.len() {
+                push(code_points, pos, &mut out);
+            }
+            Cow::Owned(out)
+        }
+    }
+}
+
+pub fn lowercase_cow(s: &str) -> Cow<'_, str> {
+    str_if_changed(s, |cps| to_lowercase_cow(cps, CaseLocale::Root))
+}
+
+pub fn uppercase_cow(s: &str) -> Cow<'_, str> {
+    str_if_changed(s, |cps| to_uppercase_cow(cps, CaseLocale::Root))
+}
+
+pub fn fold_cow(s: &str) -> Cow<'_, str> {
+    str_if_changed(s, case_fold_cow)
+}
+
+fn str_if_changed(s: &str, map: impl Fn(&[u32]) -> Cow<'_, [u32]>) -> Cow<'_, str> {
+    match map(&to_code_points(s)) {
+        Cow::Borrowed(_) => Cow::Borrowed(s),
+        Cow::Owned(mapped) => Cow::Owned(from_code_points(mapped)),
+    }
+}
+
+fn to_code_points(s: &str) -> Vec<u32> {
+    CodePointIter::new(s.as_bytes()).collect()
+}
+
+fn from_code_points(code_points: Vec<u32>) -> String {
+    let mut out = Vec::with_capacity(code_points.len());
+    for cp in code_points {
+        encode_utf8_into(cp, &mut out);
+    }
+    String::from_utf8(out).unwrap()
+}
+
 // > D145 A string X is a canonical caseless match for a string Y if and only if:
 // >      NFD(toCasefold(NFD(X))) = NFD(toCasefold(NFD(Y)))
 // The initial decomposition is to get around an edge case with combining greek ypogegrammeni, but
@@ -78,8 +528,25 @@ pub fn to_uppercase(code_points: Vec<u32>) -> Vec<u32> {
 // handling that char, and any that have it in their decomposition. That check would have to go in
 // case fold, because the point is to avoid an additional iteration. And it doesn't matter if we
 // only decompose some chars, because case folding doesn't guarantee normalisation .
-pub fn canonical_caseless_match(x: Vec<u32>, y: Vec<u32>) -> Ordering {
-    to_nfd(&case_fold(&x)).cmp(&to_nfd(&case_fold(&y)))
+// Both sides are folded and decomposed lazily, like in caseless_eq, so neither gets copied.
+pub fn canonical_caseless_match(x: &[u32], y: &[u32]) -> Ordering {
+    caseless_key(x.iter().copied()).cmp(caseless_key(y.iter().copied()))
+}
+
+pub fn canonical_caseless_match_str(x: &str, y: &str) -> Ordering {
+    caseless_cmp_utf8(x.as_bytes(), y.as_bytes())
+}
+
+// > D146 A string X is a compatibility caseless match for a string Y if and only if:
+// >      NFKD(toCasefold(NFKD(toCasefold(NFD(X))))) = NFKD(toCasefold(NFKD(toCasefold(NFD(Y)))))
+// Folding twice is needed because a few compatibility decompositions produce characters that need
+// folding again, like ㎒ → MHz. The first NFD is handled inside case_fold, as above.
+pub fn compatibility_caseless_match(x: &[u32], y: &[u32]) -> Ordering {
+    compatibility_caseless_key(x).cmp(&compatibility_caseless_key(y))
+}
+
+fn compatibility_caseless_key(code_points: &[u32]) -> Vec<u32> {
+    to_nfkd(&case_fold(&to_nfkd(&case_fold(code_points))))
 }
 
 // Case folding is a way to case-insensitively compare strings. Why not just lower/uppercase them?
@@ -88,83 +555,728 @@ pub fn canonical_caseless_match(x: Vec<u32>, y: Vec<u32>) -> Ordering {
 // against the capital SS. It's pretty simple, because it's casing without the edge cases.
 // This isn't actually enough to do string comparison, it's a first step, but it doesn't produce
 // normalised strings, so that has to happen afterwards.
-pub fn case_fold(code_points: &Vec<u32>) -> Vec<u32> {
+pub fn case_fold(code_points: &[u32]) -> Vec<u32> {
     let mut out = Vec::with_capacity(code_points.len());
-    let ypogegrammenic = vec![
-        0x1F80, 0x1F81, 0x1F82, 0x1F83, 0x1F84, 0x1F85, 0x1F86, 0x1F87, 0x1F88, 0x1F89, 0x1F8A,
-        0x1F8B, 0x1F8C, 0x1F8D, 0x1F8E, 0x1F8F, 0x1F90, 0x1F91, 0x1F92, 0x1F93, 0x1F94, 0x1F95,
-        0x1F96, 0x1F97, 0x1F98, 0x1F99, 0x1F9A, 0x1F9B, 0x1F9C, 0x1F9D, 0x1F9E, 0x1F9F, 0x1FA0,
-        0x1FA1, 0x1FA2, 0x1FA3, 0x1FA4, 0x1FA5, 0x1FA6, 0x1FA7, 0x1FA8, 0x1FA9, 0x1FAA, 0x1FAB,
-        0x1FAC, 0x1FAD, 0x1FAE, 0x1FAF, 0x1FB2, 0x1FB3, 0x1FB4, 0x1FB7, 0x1FBC, 0x1FC2, 0x1FC3,
-        0x1FC4, 0x1FC7, 0x1FCC, 0x1FF2, 0x1FF3, 0x1FF4, 0x1FF7, 0x1FFC,
-    ];
     for code_point in code_points {
-        if ypogegrammenic.contains(code_point) {
-            for cp in decompose(*code_point) {
-                out.extend(case_folding(cp).unwrap_or(vec![cp]));
+        out.extend(fold_code_point(*code_point));
+    }
+    out
+}
+
+fn fold_code_point(code_point: u32) -> Vec<u32> {
+    if contains_ypogegrammeni(code_point) {
+        decompose(code_point)
+            .into_iter()
+            .flat_map(|cp| case_folding(cp).unwrap_or(vec![cp]))
+            .collect()
+    } else {
+        case_folding(code_point).unwrap_or(vec![code_point])
+    }
+}
+
+// Every code point that folds to the same thing as this one, including itself, e.g. k, K and U+212A
+// KELVIN SIGN. A regex engine needs these to match a character class caselessly, since it can't
+// fold the class the way it folds the text. It's code points that fold to the same string, so ß
+// goes with ẞ, but not with "ss", which is two code points.
+pub fn case_closure(code_point: u32) -> Vec<u32> {
+    let folded = case_folding(code_point).unwrap_or(vec![code_point]);
+    let mut closure = case_folding_sources(&folded);
+    if let [single] = folded[..] {
+        closure.push(single);
+    }
+    if !closure.contains(&code_point) {
+        closure.push(code_point);
+    }
+    closure.sort_unstable();
+    closure.dedup();
+    closure
+}
+
+// The same comparison as canonical_caseless_match, but only whether they're equal. The folding and
+// decomposition happen lazily on both sides together, so it stops at the first difference, and
+// doesn't build either string in full.
+pub fn caseless_eq(x: &[u32], y: &[u32]) -> bool {
+    if x == y {
+        return true;
+    }
+    x.iter()
+        .flat_map(|cp| fold_code_point(*cp))
+        .nfd()
+        .eq(y.iter().flat_map(|cp| fold_code_point(*cp)).nfd())
+}
+
+// The one to use for comparing things like user names.
+pub fn caseless_eq_str(x: &str, y: &str) -> bool {
+    if x == y {
+        return true;
+    }
+    caseless_key(CodePointIter::new(x.as_bytes()))
+        .eq(caseless_key(CodePointIter::new(y.as_bytes())))
+}
+
+fn caseless_key(code_points: impl Iterator<Item = u32>) -> impl Iterator<Item = u32> {
+    code_points.flat_map(fold_code_point).nfd()
+}
+
+// canonical_caseless_match for UTF-8 bytes, for sorting or matching lots of strings. Each side is
+// decoded, folded, and decomposed as one lazy pipeline, and compared as it goes, so the only
+// buffers are the folding of one code point and the combining sequence being reordered. It stops
+// at the first difference. Invalid sequences are decoded as U+FFFD, like fix does, so they compare
+// equal to each other and to a real U+FFFD.
+pub fn caseless_cmp_utf8(x: &[u8], y: &[u8]) -> Ordering {
+    caseless_key(LossyCodePointIter::new(x)).cmp(caseless_key(LossyCodePointIter::new(y)))
+}
+
+// A hash that's the same for strings that are canonical caseless matches, so they can be used as
+// keys in a case-insensitive map. Like caseless_eq it folds and decomposes lazily, and hashes the
+// code points as they come out, so the folded string never gets built. It's FNV-1a, like
+// Collator::hash, so it stays the same between releases.
+pub fn folded_hash(s: &str) -> u64 {
+    let folded = CodePointIter::new(s.as_bytes())
+        .flat_map(fold_code_point)
+        .nfd();
+    fnv1a(folded.flat_map(|cp| cp.to_be_bytes()))
+}
+
+// NFKC_Casefold is what identifiers are compared with (and IDNA uses it too). The NFKC_CF property
+// already combines the compatibility decomposition, the case folding, and removing the default
+// ignorables, for each code point. So the string only has to be mapped, and then put back into NFC.
+pub fn to_nfkc_casefold(code_points: &[u32]) -> Vec<u32> {
+    let mapped: Vec<u32> = code_points
+        .iter()
+        .flat_map(|cp| nfkc_casefold(*cp).unwrap_or(vec![*cp]))
+        .collect();
+    to_nfc(&mapped)
+}
+
+// Lazy versions of the root case mappings, for chaining onto iterators of code points. Upper-casing
+// and folding are one code point at a time. Lower-casing is too, except for the final sigma, which
+// needs to know whether there's a cased letter before it, and whether there's one after it once
+// any case-ignorables are skipped. The first is just a flag, and for the second, it reads ahead
+// through the case-ignorables and holds on to them until they've been mapped.
+pub trait CaseIter: Iterator<Item = u32> + Sized {
+    fn to_lowercase_cps(self) -> Lowercase<Self> {
+        Lowercase {
+            code_points: self,
+            lookahead: VecDeque::new(),
+            after_cased: false,
+            ready: VecDeque::new(),
+        }
+    }
+
+    fn to_uppercase_cps(self) -> Uppercase<Self> {
+        Uppercase {
+            code_points: self,
+            ready: VecDeque::new(),
+        }
+    }
+
+    fn case_fold_cps(self) -> CaseFold<Self> {
+        CaseFold {
+            code_points: self,
+            ready: VecDeque::new(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = u32>> CaseIter for I {}
+
+pub struct Lowercase<I> {
+    code_points: I,
+    lookahead: VecDeque<u32>,
+    after_cased: bool,
+    ready: VecDeque<u32>,
+}
+
+impl<I: Iterator<Item = u32>> Lowercase<I> {
+    fn next_input(&mut self) -> Option<u32> {
+        self.lookahead
+            .pop_front()
+            .or_else(|| self.code_points.next())
+    }
+
+    fn is_followed_by_cased(&mut self) -> bool {
+        let mut i = 0;
+        loop {
+            if i == self.lookahead.len() {
+                match self.code_points.next() {
+                    Some(cp) => self.lookahead.push_back(cp),
+                    None => return false,
+                }
             }
-        } else {
-            out.extend(case_folding(*code_point).unwrap_or(vec![*code_point]));
+            if !case_ignorable(self.lookahead[i]) {
+                return cased(self.lookahead[i]);
+            }
+            i += 1;
         }
     }
-    out
+}
+
+impl<I: Iterator<Item = u32>> Iterator for Lowercase<I> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(cp) = self.ready.pop_front() {
+            return Some(cp);
+        }
+        let code_point = self.next_input()?;
+        let final_sigma = special_casing(code_point)
+            .iter()
+            .find(|special| special.context == Some(CasingContext::FinalSigma));
+        match final_sigma {
+            Some(special) if self.after_cased && !self.is_followed_by_cased() => {
+                self.ready.extend(&special.lower)
+            }
+            _ => self
+                .ready
+                .extend(lowercase_mapping(code_point).unwrap_or(vec![code_point])),
+        }
+        if !case_ignorable(code_point) {
+            self.after_cased = cased(code_point);
+        }
+        self.ready.pop_front()
+    }
+}
+
+pub struct Uppercase<I> {
+    code_points: I,
+    ready: VecDeque<u32>,
+}
+
+impl<I: Iterator<Item = u32>> Iterator for Uppercase<I> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ready.is_empty() {
+            let code_point = self.code_points.next()?;
+            self.ready
+                .extend(uppercase_mapping(code_point).unwrap_or(vec![code_point]));
+        }
+        self.ready.pop_front()
+    }
+}
+
+pub struct CaseFold<I> {
+    code_points: I,
+    ready: VecDeque<u32>,
+}
+
+impl<I: Iterator<Item = u32>> Iterator for CaseFold<I> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ready.is_empty() {
+            let code_point = self.code_points.next()?;
+            self.ready.extend(fold_code_point(code_point));
+        }
+        self.ready.pop_front()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::chars::{from_chars, to_string};
 
     #[test]
     fn test_to_lowercase() {
-        assert_eq!(to_lowercase(vec![0x0130]), vec![105, 775]);
-        assert_eq!(to_lowercase(vec![0x011E]), vec![0x011F]);
+        assert_eq!(to_lowercase(vec![0x0130], CaseLocale::Root), vec![105, 775]);
+        assert_eq!(to_lowercase(vec![0x011E], CaseLocale::Root), vec![0x011F]);
 
         // SIGMA - σ
-        assert_eq!(to_lowercase(vec![0x03A3]), vec![0x03C3]);
+        assert_eq!(to_lowercase(vec![0x03A3], CaseLocale::Root), vec![0x03C3]);
         // YPOGEGRAMMENI (ignorable) SIGMA SPACE - ignorable sigma not-cased - σ
         assert_eq!(
-            to_lowercase(vec![0x0345, 0x03A3, 0x0020]),
+            to_lowercase(vec![0x0345, 0x03A3, 0x0020], CaseLocale::Root),
             vec![0x0345, 0x03C3, 0x0020]
         );
         // ALPHA YPOGEGRAMMENI SIGMA FULL-STOP BETA - cased ignorable sigma ignorable cased - σ
         assert_eq!(
-            to_lowercase(vec![0x0391, 0x0345, 0x03A3, 0x002E, 0x0392]),
+            to_lowercase(
+                vec![0x0391, 0x0345, 0x03A3, 0x002E, 0x0392],
+                CaseLocale::Root
+            ),
             vec![0x03B1, 0x0345, 0x03C3, 0x002E, 0x03B2]
         );
         // ALPHA YPOGEGRAMMENI SIGMA SPACE - cased ignorable sigma not-cased - ς
         assert_eq!(
-            to_lowercase(vec![0x0391, 0x0345, 0x03A3, 0x0020]),
+            to_lowercase(vec![0x0391, 0x0345, 0x03A3, 0x0020], CaseLocale::Root),
             vec![0x03B1, 0x0345, 0x03C2, 0x0020]
         );
         // ALPHA YPOGEGRAMMENI SIGMA - cased ignorable sigma - ς
         assert_eq!(
-            to_lowercase(vec![0x0391, 0x0345, 0x03A3]),
+            to_lowercase(vec![0x0391, 0x0345, 0x03A3], CaseLocale::Root),
             vec![0x03B1, 0x0345, 0x03C2]
         );
     }
 
+    #[test]
+    fn test_case_locale() {
+        use CaseLocale::*;
+        // I → ı, but İ → i, and I + dot above → i
+        assert_eq!(to_lowercase(vec![0x0049], Turkish), [0x0131]);
+        assert_eq!(to_lowercase(vec![0x0130], Azerbaijani), [0x0069]);
+        assert_eq!(to_lowercase(vec![0x0049, 0x0307], Turkish), [0x0069]);
+        assert_eq!(
+            to_lowercase(vec![0x0049, 0x0316, 0x0307], Turkish),
+            [0x0069, 0x0316]
+        );
+        assert_eq!(
+            to_uppercase(vec![0x0069, 0x0131], Turkish),
+            [0x0130, 0x0049]
+        );
+
+        // Lithuanian keeps the dot when there's an accent above
+        assert_eq!(
+            to_lowercase(vec![0x0049, 0x0301], Lithuanian),
+            [0x0069, 0x0307, 0x0301]
+        );
+        assert_eq!(
+            to_lowercase(vec![0x0049, 0x0062], Lithuanian),
+            [0x0069, 0x0062]
+        );
+        assert_eq!(
+            to_lowercase(vec![0x00CC], Lithuanian),
+            [0x0069, 0x0307, 0x0300]
+        );
+        assert_eq!(
+            to_uppercase(vec![0x0069, 0x0307, 0x0301], Lithuanian),
+            [0x0049, 0x0301]
+        );
+        assert_eq!(
+            to_uppercase(vec![0x0061, 0x0307], Lithuanian),
+            [0x0041, 0x0307]
+        );
+
+        assert_eq!(to_lowercase(vec![0x0049], Root), [0x0069]);
+        assert_eq!(to_uppercase(vec![0x0069], Root), [0x0049]);
+    }
+
+    #[test]
+    fn test_greek_uppercase() {
+        let uppercase = |s: &str, locale: CaseLocale| -> String {
+            to_string(&to_uppercase(from_chars(s.chars()), locale)).unwrap()
+        };
+        assert_eq!(uppercase("Μαΐου", CaseLocale::Greek), "ΜΑΪΟΥ");
+        assert_eq!(
+            uppercase("Μαΐου", CaseLocale::Root),
+            "ΜΑ\u{0399}\u{0308}\u{0301}ΟΥ"
+        );
+        assert_eq!(uppercase("άδικος", CaseLocale::Greek), "ΑΔΙΚΟΣ");
+        // the accent shows it isn't a diphthong, so it becomes a dialytika
+        assert_eq!(uppercase("πάιδακια", CaseLocale::Greek), "ΠΑΪΔΑΚΙΑ");
+        assert_eq!(uppercase("παιδιά", CaseLocale::Greek), "ΠΑΙΔΙΑ");
+        // breathings and the ypogegrammeni
+        assert_eq!(uppercase("ᾅδης", CaseLocale::Greek), "ΑΙΔΗΣ");
+        // ή on its own keeps its accent
+        assert_eq!(uppercase("ή ήταν", CaseLocale::Greek), "Ή ΗΤΑΝ");
+        // non-Greek letters are left alone
+        assert_eq!(uppercase("café", CaseLocale::Greek), "CAFÉ");
+    }
+
+    #[test]
+    fn test_case_map_range() {
+        // ΟΔΟΣ ΟΔΟΣ with only the first ΟΔΟΣ selected, and then only ΟΔΟΣ ΟΔ
+        let text = vec![
+            0x039F, 0x0394, 0x039F, 0x03A3, 0x0020, 0x039F, 0x0394, 0x039F, 0x03A3,
+        ];
+        assert_eq!(
+            case_map_range(&text, 0..4, CaseOp::Lowercase),
+            [0x03BF, 0x03B4, 0x03BF, 0x03C2, 0x0020, 0x039F, 0x0394, 0x039F, 0x03A3]
+        );
+        // ΟΔΟΣ ΟΔ, the second sigma isn't final, even though the selection ends after it
+        let text = vec![0x039F, 0x0394, 0x039F, 0x03A3, 0x039F, 0x0394];
+        assert_eq!(
+            case_map_range(&text, 0..4, CaseOp::Lowercase),
+            [0x03BF, 0x03B4, 0x03BF, 0x03C3, 0x039F, 0x0394]
+        );
+        // abc → aBc, and ß can change the length
+        assert_eq!(
+            case_map_range(&[0x61, 0x62, 0x63], 1..2, CaseOp::Uppercase),
+            [0x61, 0x42, 0x63]
+        );
+        assert_eq!(
+            case_map_range(&[0x41, 0xDF, 0x42], 1..3, CaseOp::Fold),
+            [0x41, 0x73, 0x73, 0x62]
+        );
+        assert_eq!(case_map_range(&[0x41], 1..1, CaseOp::Lowercase), [0x41]);
+    }
+
+    #[test]
+    fn test_caseless_cmp_utf8() {
+        assert_eq!(
+            caseless_cmp_utf8("Straße".as_bytes(), "STRASSE".as_bytes()),
+            Ordering::Equal
+        );
+        // Å as one code point, and as A + ring
+        assert_eq!(
+            caseless_cmp_utf8("\u{00C5}".as_bytes(), "a\u{030A}".as_bytes()),
+            Ordering::Equal
+        );
+        assert_eq!(
+            caseless_cmp_utf8(b"apple", b"BANANA"),
+            canonical_caseless_match_str("apple", "BANANA")
+        );
+        assert_eq!(caseless_cmp_utf8(b"abc", b"AB"), Ordering::Greater);
+        assert_eq!(caseless_cmp_utf8(b"", b""), Ordering::Equal);
+        assert_eq!(caseless_cmp_utf8(b"A\xC3", b"a\xFF"), Ordering::Equal);
+        assert_eq!(
+            caseless_cmp_utf8(b"x\xE0\x80", "X\u{FFFD}\u{FFFD}".as_bytes()),
+            Ordering::Equal
+        );
+        assert_eq!(caseless_cmp_utf8(b"\xF5", b"z"), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_case_cow() {
+        assert!(matches!(lowercase_cow("already lower"), Cow::Borrowed(_)));
+        assert_eq!(lowercase_cow("Not Lower"), "not lower");
+        assert!(matches!(uppercase_cow("ABC 123"), Cow::Borrowed(_)));
+        assert_eq!(uppercase_cow("straße"), "STRASSE");
+        assert!(matches!(fold_cow("folded"), Cow::Borrowed(_)));
+        assert_eq!(fold_cow("Folded"), "folded");
+        // the sigma only changes context after the first change
+        assert_eq!(lowercase_cow("οδοΣ"), "οδος");
+        // i has a Turkish mapping, but it doesn't change when lowercasing
+        let text = [0x0069, 0x0061];
+        assert!(matches!(
+            to_lowercase_cow(&text, CaseLocale::Turkish),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(
+            to_uppercase_cow(&text, CaseLocale::Turkish).into_owned(),
+            [0x0130, 0x0041]
+        );
+        // Greek capitals lose their accents, and plain ones stay borrowed
+        assert!(matches!(
+            to_uppercase_cow(&[0x0391], CaseLocale::Greek),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(
+            to_uppercase_cow(&[0x0386], CaseLocale::Greek).into_owned(),
+            [0x0391]
+        );
+    }
+
+    #[test]
+    fn test_case_closure() {
+        // k, K, KELVIN SIGN
+        assert_eq!(case_closure(0x006B), [0x004B, 0x006B, 0x212A]);
+        assert_eq!(case_closure(0x212A), [0x004B, 0x006B, 0x212A]);
+        // σ, ς, Σ
+        assert_eq!(case_closure(0x03C2), [0x03A3, 0x03C2, 0x03C3]);
+        // ß and ẞ both fold to ss
+        assert_eq!(case_closure(0x00DF), [0x00DF, 0x1E9E]);
+        // uncased
+        assert_eq!(case_closure(0x0031), [0x0031]);
+    }
+
+    #[test]
+    fn test_sigma_mode() {
+        use SigmaMode::*;
+        // ΑΣ1, the digit is part of the word
+        let digit = vec![0x0391, 0x03A3, 0x0031];
+        assert_eq!(
+            to_lowercase_with_sigma(digit.clone(), CaseLocale::Root, Standard),
+            [0x03B1, 0x03C2, 0x0031]
+        );
+        assert_eq!(
+            to_lowercase_with_sigma(digit, CaseLocale::Root, WordBoundary),
+            [0x03B1, 0x03C3, 0x0031]
+        );
+        // ΑΣ^Β, the ^ is case-ignorable, but it's between two words
+        let caret = vec![0x0391, 0x03A3, 0x005E, 0x0392];
+        assert_eq!(
+            to_lowercase_with_sigma(caret.clone(), CaseLocale::Root, Standard),
+            [0x03B1, 0x03C3, 0x005E, 0x03B2]
+        );
+        assert_eq!(
+            to_lowercase_with_sigma(caret, CaseLocale::Root, WordBoundary),
+            [0x03B1, 0x03C2, 0x005E, 0x03B2]
+        );
+        // They agree on the simple cases: ΑΣ Σ
+        let words = vec![0x0391, 0x03A3, 0x0020, 0x03A3];
+        assert_eq!(
+            to_lowercase_with_sigma(words.clone(), CaseLocale::Root, WordBoundary),
+            to_lowercase(words, CaseLocale::Root)
+        );
+    }
+
+    #[test]
+    fn test_special_casing() {
+        // 0307 has a Lithuanian entry, and one each for Turkish and Azerbaijani
+        assert_eq!(special_casing(0x0307).len(), 3);
+        assert!(special_casing(0x0041).is_empty());
+        let sigma = &special_casing(0x03A3)[0];
+        assert_eq!(sigma.language, None);
+        assert_eq!(sigma.context, Some(CasingContext::FinalSigma));
+        assert_eq!(sigma.lower, [0x03C2]);
+
+        // ᾳ and everything else with an ypogegrammeni in it, but not the ypogegrammeni itself
+        assert!(contains_ypogegrammeni(0x1FB3));
+        assert!(contains_ypogegrammeni(0x1FFC));
+        assert!(!contains_ypogegrammeni(0x0345));
+        assert!(!contains_ypogegrammeni(0x1F00));
+    }
+
+    #[test]
+    fn test_compatibility_caseless_match() {
+        // ﬁle, FILE
+        assert_eq!(
+            compatibility_caseless_match(&[0xFB01, 0x6C, 0x65], &[0x46, 0x49, 0x4C, 0x45]),
+            Ordering::Equal
+        );
+        // ＦＩＬＥ in full width only matches when compatibility decomposed
+        assert_eq!(
+            compatibility_caseless_match(
+                &[0xFF26, 0xFF29, 0xFF2C, 0xFF25],
+                &[0x66, 0x69, 0x6C, 0x65]
+            ),
+            Ordering::Equal
+        );
+        assert_ne!(
+            canonical_caseless_match(&[0xFF26, 0xFF29, 0xFF2C, 0xFF25], &[0x66, 0x69, 0x6C, 0x65]),
+            Ordering::Equal
+        );
+        // ㎒ needs folding again after it's decomposed to MHz
+        assert_eq!(
+            compatibility_caseless_match(&[0x3392], &[0x6D, 0x68, 0x7A]),
+            Ordering::Equal
+        );
+        assert_ne!(
+            compatibility_caseless_match(&[0x61], &[0x62]),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn test_canonical_caseless_match() {
+        assert_eq!(
+            canonical_caseless_match_str("Straße", "STRASSE"),
+            Ordering::Equal
+        );
+        assert_eq!(
+            canonical_caseless_match_str("apple", "Banana"),
+            Ordering::Less
+        );
+        // ᾈ is ἀ + ypogegrammeni, which folds to ι
+        assert_eq!(
+            canonical_caseless_match(&[0x1F88], &[0x1F00, 0x03B9]),
+            Ordering::Equal
+        );
+        assert!(caseless_eq_str("Ångström", "a\u{030A}NGSTRÖM"));
+        assert!(!caseless_eq_str("login", "logan"));
+    }
+
+    #[test]
+    fn test_caseless_eq() {
+        // Straße, STRASSE
+        assert!(caseless_eq(
+            &[0x53, 0x74, 0x72, 0x61, 0xDF, 0x65],
+            &[0x53, 0x54, 0x52, 0x41, 0x53, 0x53, 0x45]
+        ));
+        // Å, a + ring above
+        assert!(caseless_eq(&[0x00C5], &[0x61, 0x030A]));
+        // ᾈ, ἀι
+        assert!(caseless_eq(&[0x1F88], &[0x1F00, 0x03B9]));
+        assert!(!caseless_eq(&[0x61, 0x62], &[0x41, 0x43]));
+        assert!(!caseless_eq(&[0x61], &[0x41, 0x41]));
+    }
+
+    #[test]
+    fn test_folded_hash() {
+        assert_eq!(folded_hash("Straße"), folded_hash("STRASSE"));
+        assert_eq!(
+            folded_hash("\u{00C5}ngstr\u{00F6}m"),
+            folded_hash("A\u{030A}NGSTRO\u{0308}M")
+        );
+        assert_ne!(folded_hash("abc"), folded_hash("abd"));
+        assert_eq!(folded_hash(""), 0xcbf29ce484222325);
+    }
+
+    #[test]
+    fn test_str_case() {
+        assert_eq!(lowercase("ΟΔΟΣ Straße"), "οδος straße");
+        assert_eq!(uppercase("ΟΔΟΣ Straße"), "ΟΔΟΣ STRASSE");
+        assert_eq!(fold("ΟΔΟΣ Straße"), "οδοσ strasse");
+        assert_eq!(lowercase(""), "");
+    }
+
+    #[test]
+    fn test_case_iter() {
+        let cases: [&[u32]; 6] = [
+            &[0x03A3],
+            &[0x0345, 0x03A3, 0x0020],
+            &[0x0391, 0x0345, 0x03A3, 0x002E, 0x0392],
+            &[0x0391, 0x0345, 0x03A3, 0x0020],
+            &[0x0391, 0x0345, 0x03A3],
+            &[0x0391, 0x03A3, 0x03A3, 0x0130, 0x00DF, 0x1F88],
+        ];
+        for case in cases {
+            assert_eq!(
+                case.iter()
+                    .copied()
+                    .to_lowercase_cps()
+                    .collect::<Vec<u32>>(),
+                to_lowercase(case.to_vec(), CaseLocale::Root)
+            );
+            assert_eq!(
+                case.iter()
+                    .copied()
+                    .to_uppercase_cps()
+                    .collect::<Vec<u32>>(),
+                to_uppercase(case.to_vec(), CaseLocale::Root)
+            );
+            assert_eq!(
+                case.iter().copied().case_fold_cps().collect::<Vec<u32>>(),
+                case_fold(case)
+            );
+        }
+    }
+
+    #[test]
+    fn test_swapcase() {
+        // Hello World!
+        assert_eq!(
+            swapcase(vec![0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x20, 0x57, 0x21]),
+            [0x68, 0x45, 0x4C, 0x4C, 0x4F, 0x20, 0x77, 0x21]
+        );
+        // ǅ → ǆ, ß → SS
+        assert_eq!(swapcase(vec![0x01C5, 0x00DF]), [0x01C6, 0x53, 0x53]);
+        // ΣαΣ → σΑς, ΣΑΣ → σας
+        assert_eq!(
+            swapcase(vec![0x03A3, 0x03B1, 0x03A3]),
+            [0x03C3, 0x0391, 0x03C2]
+        );
+        assert_eq!(
+            swapcase(vec![0x03A3, 0x0391, 0x03A3]),
+            [0x03C3, 0x03B1, 0x03C2]
+        );
+    }
+
+    #[test]
+    fn test_to_titlecase() {
+        let titlecase = |s: &str| -> String {
+            to_string(&to_titlecase(from_chars(s.chars()), CaseLocale::Root)).unwrap()
+        };
+        assert_eq!(titlecase("hello WORLD"), "Hello World");
+        assert_eq!(
+            titlecase("can't stop, won't stop"),
+            "Can't Stop, Won't Stop"
+        );
+        // the first cased letter gets titlecased, even if it's not the first thing in the word
+        assert_eq!(titlecase("'quoted' 3rd"), "'Quoted' 3Rd");
+        // ǆ has its own titlecase form, and ß and ﬁ titlecase to two code points
+        assert_eq!(titlecase("\u{01C6}emal"), "\u{01C5}emal");
+        assert_eq!(titlecase("\u{00DF}traße"), "Sstraße");
+        assert_eq!(titlecase("\u{FB01}ne"), "Fine");
+        // the sigma at the end of the word is still final
+        assert_eq!(titlecase("ΟΔΟΣ ΟΔΟΣ"), "Οδος Οδος");
+    }
+
+    #[test]
+    fn test_titlecase_sentences() {
+        assert_eq!(
+            titlecase_sentences("hello there! how are you? i'm fine! (really.) Ok, bye."),
+            "Hello there! How are you? I'm fine! (Really.) Ok, bye."
+        );
+        // Only the first letter changes, and it looks past punctuation and digits.
+        assert_eq!(
+            titlecase_sentences("\"the NASA one,\" she said! 3 eggs."),
+            "\"The NASA one,\" she said! 3 Eggs."
+        );
+        // Abbreviations followed by lowercase don't end the sentence.
+        assert_eq!(
+            titlecase_sentences("see e.g. the docs"),
+            "See e.g. the docs"
+        );
+        assert_eq!(titlecase_sentences("ǆungla! ﬁne."), "ǅungla! Fine.");
+        assert_eq!(
+            titlecase_sentences("line one\nline two"),
+            "Line one\nLine two"
+        );
+        assert_eq!(titlecase_sentences(""), "");
+    }
+
+    #[test]
+    fn test_to_titlecase_locale() {
+        let titlecase = |s: &str, locale: CaseLocale| -> String {
+            to_string(&to_titlecase(from_chars(s.chars()), locale)).unwrap()
+        };
+        // IJ is one letter in Dutch, but only at the start of the word
+        assert_eq!(titlecase("ijsland", CaseLocale::Dutch), "IJsland");
+        assert_eq!(titlecase("IJSLAND", CaseLocale::Dutch), "IJsland");
+        assert_eq!(titlecase("'ijs' bij", CaseLocale::Dutch), "'IJs' Bij");
+        assert_eq!(titlecase("ijsland", CaseLocale::Root), "Ijsland");
+        // the Turkish i keeps its dot, and I loses it
+        assert_eq!(
+            titlecase("istanbul IŞIK", CaseLocale::Turkish),
+            "İstanbul Işık"
+        );
+    }
+
     #[test]
     fn test_to_uppercase() {
         // ß -> SS
-        assert_eq!(to_uppercase(vec![0x00DF]), vec![0x0053, 0x0053]);
+        assert_eq!(
+            to_uppercase(vec![0x00DF], CaseLocale::Root),
+            vec![0x0053, 0x0053]
+        );
         // ŉ -> ʼN
-        assert_eq!(to_uppercase(vec![0x0149]), vec![0x02BC, 0x004E]);
+        assert_eq!(
+            to_uppercase(vec![0x0149], CaseLocale::Root),
+            vec![0x02BC, 0x004E]
+        );
         // ǰ -> J̌
-        assert_eq!(to_uppercase(vec![0x01F0]), vec![0x004A, 0x030C]);
+        assert_eq!(
+            to_uppercase(vec![0x01F0], CaseLocale::Root),
+            vec![0x004A, 0x030C]
+        );
         // ΐ -> Ϊ́
-        assert_eq!(to_uppercase(vec![0x0390]), vec![0x0399, 0x0308, 0x0301]);
+        assert_eq!(
+            to_uppercase(vec![0x0390], CaseLocale::Root),
+            vec![0x0399, 0x0308, 0x0301]
+        );
         // ΰ -> Ϋ́
-        assert_eq!(to_uppercase(vec![0x03B0]), vec![0x03A5, 0x0308, 0x0301]);
+        assert_eq!(
+            to_uppercase(vec![0x03B0], CaseLocale::Root),
+            vec![0x03A5, 0x0308, 0x0301]
+        );
         // և -> ԵՒ
-        assert_eq!(to_uppercase(vec![0x0587]), vec![0x0535, 0x0552]);
+        assert_eq!(
+            to_uppercase(vec![0x0587], CaseLocale::Root),
+            vec![0x0535, 0x0552]
+        );
         // ẖ -> H̱
-        assert_eq!(to_uppercase(vec![0x1E96]), vec![0x0048, 0x0331]);
+        assert_eq!(
+            to_uppercase(vec![0x1E96], CaseLocale::Root),
+            vec![0x0048, 0x0331]
+        );
         // ﬄ -> FFL
-        assert_eq!(to_uppercase(vec![0xFB04]), vec![0x0046, 0x0046, 0x004C]);
+        assert_eq!(
+            to_uppercase(vec![0xFB04], CaseLocale::Root),
+            vec![0x0046, 0x0046, 0x004C]
+        );
         // . -> .
-        assert_eq!(to_uppercase(vec![0x002E]), vec![0x002E]);
+        assert_eq!(to_uppercase(vec![0x002E], CaseLocale::Root), vec![0x002E]);
     }
 
     #[test]
     fn test_case_fold() {}
+
+    #[test]
+    fn test_to_nfkc_casefold() {
+        // ﬁ -> fi
+        assert_eq!(to_nfkc_casefold(&[0xFB01]), vec![0x66, 0x69]);
+        // ẞ -> ss
+        assert_eq!(to_nfkc_casefold(&[0x1E9E]), vec![0x73, 0x73]);
+        // Å (ANGSTROM SIGN) -> å
+        assert_eq!(to_nfkc_casefold(&[0x212B]), vec![0x00E5]);
+        // A SOFT-HYPHEN B -> ab
+        assert_eq!(to_nfkc_casefold(&[0x41, 0x00AD, 0x42]), vec![0x61, 0x62]);
+        // A COMBINING-RING -> å, recomposed
+        assert_eq!(to_nfkc_casefold(&[0x41, 0x030A]), vec![0x00E5]);
+        // ² -> 2
+        assert_eq!(to_nfkc_casefold(&[0x00B2]), vec![0x32]);
+    }
 }
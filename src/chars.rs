@@ -0,0 +1,62 @@
+// Going between the u32 code points the rest of the crate works with and std's char. A char is a
+// scalar value, i.e. any code point but the surrogates, so going from u32 to char can fail, with
+// the same error a surrogate or anything above U+10FFFF gets when decoding.
+
+use crate::helpers::DecodeErr;
+
+pub fn to_char(code_point: u32) -> Result<char, DecodeErr> {
+    char::from_u32(code_point).ok_or(DecodeErr::InvalidCodePoint)
+}
+
+pub fn try_chars<I: IntoIterator<Item = u32>>(
+    code_points: I,
+) -> impl Iterator<Item = Result<char, DecodeErr>> {
+    code_points.into_iter().map(to_char)
+}
+
+// Errors come with the index of the code point that isn't a scalar value.
+pub fn to_string(code_points: &[u32]) -> Result<String, (DecodeErr, usize)> {
+    code_points
+        .iter()
+        .enumerate()
+        .map(|(i, cp)| to_char(*cp).map_err(|err| (err, i)))
+        .collect()
+}
+
+// E.g. from_chars(s.chars()), for passing a &str to anything that takes code points.
+pub fn from_chars<I: IntoIterator<Item = char>>(chars: I) -> Vec<u32> {
+    chars.into_iter().map(|c| c as u32).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_char() {
+        assert_eq!(to_char(0x61), Ok('a'));
+        assert_eq!(to_char(0x10FFFF), Ok('\u{10FFFF}'));
+        assert_eq!(to_char(0xD800), Err(DecodeErr::InvalidCodePoint));
+        assert_eq!(to_char(0xDFFF), Err(DecodeErr::InvalidCodePoint));
+        assert_eq!(to_char(0x110000), Err(DecodeErr::InvalidCodePoint));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let s = "ศไทย中华Việt Nam \u{1F600}";
+        let code_points = from_chars(s.chars());
+        assert_eq!(code_points.len(), s.chars().count());
+        assert_eq!(to_string(&code_points), Ok(s.to_string()));
+        assert_eq!(
+            try_chars(code_points).collect::<Result<String, _>>(),
+            Ok(s.to_string())
+        );
+
+        assert_eq!(
+            to_string(&[0x61, 0x62, 0xDE00]),
+            Err((DecodeErr::InvalidCodePoint, 2))
+        );
+        let chars: Vec<_> = try_chars([0x61, 0x110000]).collect();
+        assert_eq!(chars, [Ok('a'), Err(DecodeErr::InvalidCodePoint)]);
+    }
+}
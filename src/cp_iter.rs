@@ -1,49 +1,56 @@
-use crate::helpers::{decode_double, decode_quad, decode_triple, CodeUnit};
+use crate::helpers::{decode_double, decode_quad, decode_triple, CodeUnit, DecodeErr};
+use crate::validate::decode_at;
 
-pub struct CodePointIter {
-    bytes: Vec<u8>,
+// Takes either owned or borrowed bytes, so it doesn't have to copy a slice just to read it. It
+// trusts the bytes to be valid UTF-8, so it's only for the crate's own use, on input that's a str
+// or has already been validated.
+pub struct CodePointIter<B = Vec<u8>> {
+    bytes: B,
     pos: usize,
 }
 
-impl CodePointIter {
-    pub fn new(bytes: Vec<u8>) -> Self {
+impl<B: AsRef<[u8]>> CodePointIter<B> {
+    pub fn new(bytes: B) -> Self {
         CodePointIter { bytes, pos: 0 }
     }
+
+    // The byte offset of the next code point.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
 }
 
-impl Iterator for CodePointIter {
+impl<B: AsRef<[u8]>> Iterator for CodePointIter<B> {
     type Item = u32;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.pos >= self.bytes.len() {
+        let bytes = self.bytes.as_ref();
+        if self.pos >= bytes.len() {
             None
         } else {
-            match CodeUnit::try_from(self.bytes[self.pos]).unwrap() {
+            match CodeUnit::try_from(bytes[self.pos]).unwrap() {
                 CodeUnit::SingleByte => {
-                    let code_point = self.bytes[self.pos];
+                    let code_point = bytes[self.pos];
                     self.pos += 1;
                     Some(code_point as u32)
                 }
                 CodeUnit::DoublePrefix => {
-                    let code_point = decode_double(self.bytes[self.pos], self.bytes[self.pos + 1]);
+                    let code_point = decode_double(bytes[self.pos], bytes[self.pos + 1]);
                     self.pos += 2;
                     Some(code_point)
                 }
                 CodeUnit::TriplePrefix => {
-                    let code_point = decode_triple(
-                        self.bytes[self.pos],
-                        self.bytes[self.pos + 1],
-                        self.bytes[self.pos + 2],
-                    );
+                    let code_point =
+                        decode_triple(bytes[self.pos], bytes[self.pos + 1], bytes[self.pos + 2]);
                     self.pos += 3;
                     Some(code_point)
                 }
                 CodeUnit::QuadPrefix => {
                     let code_point = decode_quad(
-                        self.bytes[self.pos],
-                        self.bytes[self.pos + 1],
-                        self.bytes[self.pos + 2],
-                        self.bytes[self.pos + 3],
+                        bytes[self.pos],
+                        bytes[self.pos + 1],
+                        bytes[self.pos + 2],
+                        bytes[self.pos + 3],
                     );
                     self.pos += 4;
                     Some(code_point)
@@ -53,3 +60,140 @@ impl Iterator for CodePointIter {
         }
     }
 }
+
+// For bytes that haven't been validated, since CodePointIter panics on anything that isn't UTF-8.
+// Errors come with the length of the maximal subpart, which it skips, so the next item is whatever
+// comes after it.
+pub struct TryCodePointIter<B = Vec<u8>> {
+    bytes: B,
+    pos: usize,
+}
+
+impl<B: AsRef<[u8]>> TryCodePointIter<B> {
+    pub fn new(bytes: B) -> Self {
+        TryCodePointIter { bytes, pos: 0 }
+    }
+
+    // The byte offset of the next code point, or error.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+impl<B: AsRef<[u8]>> Iterator for TryCodePointIter<B> {
+    type Item = Result<u32, (DecodeErr, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = self.bytes.as_ref();
+        if self.pos >= bytes.len() {
+            return None;
+        }
+        match decode_at(bytes, self.pos) {
+            Ok((code_point, len)) => {
+                self.pos += len;
+                Some(Ok(code_point))
+            }
+            Err((err, len)) => {
+                self.pos += len;
+                Some(Err((err, len)))
+            }
+        }
+    }
+}
+
+// Decodes anything, replacing bad sequences with U+FFFD the same way fix does, but as it goes, so
+// there's no need to fix the whole input first.
+pub struct LossyCodePointIter<B = Vec<u8>> {
+    bytes: B,
+    pos: usize,
+}
+
+impl<B: AsRef<[u8]>> LossyCodePointIter<B> {
+    pub fn new(bytes: B) -> Self {
+        LossyCodePointIter { bytes, pos: 0 }
+    }
+
+    pub fn chars(self) -> impl Iterator<Item = char> {
+        self.map(|cp| char::from_u32(cp).unwrap())
+    }
+}
+
+impl<B: AsRef<[u8]>> Iterator for LossyCodePointIter<B> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = self.bytes.as_ref();
+        if self.pos >= bytes.len() {
+            return None;
+        }
+        match decode_at(bytes, self.pos) {
+            Ok((code_point, len)) => {
+                self.pos += len;
+                Some(code_point)
+            }
+            Err((_, len)) => {
+                self.pos += len;
+                Some(0xFFFD)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fix::fix;
+
+    #[test]
+    fn test_try_code_point_iter() {
+        let mut iter = TryCodePointIter::new(b"a\x80\xE2\x82\xAC\xC0\xAF\xE2\x82");
+        assert_eq!(iter.next(), Some(Ok(0x61)));
+        assert_eq!(
+            iter.next(),
+            Some(Err((DecodeErr::UnexpectedContinuation, 1)))
+        );
+        assert_eq!(iter.next(), Some(Ok(0x20AC)));
+        assert_eq!(iter.next(), Some(Err((DecodeErr::OverlongEncoding, 1))));
+        assert_eq!(
+            iter.next(),
+            Some(Err((DecodeErr::UnexpectedContinuation, 1)))
+        );
+        assert_eq!(iter.next(), Some(Err((DecodeErr::IncompleteCharacter, 2))));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.position(), 9);
+
+        // The same thing that panics CodePointIter.
+        let results: Vec<_> = TryCodePointIter::new(b"\xE2").collect();
+        assert_eq!(results, [Err((DecodeErr::IncompleteCharacter, 1))]);
+    }
+
+    #[test]
+    fn test_lossy_code_point_iter() {
+        let lossy: Vec<u32> = LossyCodePointIter::new(b"a\x80b\xE2\x82c\xFF").collect();
+        assert_eq!(lossy, [0x61, 0xFFFD, 0x62, 0xFFFD, 0x63, 0xFFFD]);
+        // A truncated character at the end.
+        let lossy: Vec<u32> = LossyCodePointIter::new(b"ab\xF0\x9F\x98").collect();
+        assert_eq!(lossy, [0x61, 0x62, 0xFFFD]);
+        assert_eq!(LossyCodePointIter::new(b"").next(), None);
+
+        let inputs: [&[u8]; 6] = [
+            b"Hello\xC0\x80 There\xE6\x83 Goodbye",
+            b"\xF5foo\xF5\x80bar",
+            b"\xF1foo\xF1\x80bar\xF1\x80\x80baz",
+            b"\xF0\x80\x80\x80foo\xF0\x90\x80\x80bar",
+            b"\xED\xA0\x80foo\xED\xBF\xBFbar",
+            "ศไทย中华Việt Nam".as_bytes(),
+        ];
+        for input in inputs {
+            let fixed: Vec<u32> = CodePointIter::new(fix(input).0).collect();
+            let lossy: Vec<u32> = LossyCodePointIter::new(input).collect();
+            assert_eq!(lossy, fixed);
+        }
+    }
+
+    #[test]
+    fn test_chars() {
+        let lossy: String = LossyCodePointIter::new(b"a\xFFb").chars().collect();
+        assert_eq!(lossy, "a\u{FFFD}b");
+    }
+}
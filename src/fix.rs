@@ -1,106 +1,193 @@
 use crate::helpers::*;
-use crate::validate::validate;
+use crate::validate::validate_all;
+use std::borrow::Cow;
+use std::ops::Range;
 
 const REPLACEMENT: &[u8] = &[0xEF, 0xBF, 0xBD];
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
 
-pub fn fix(input: Vec<u8>) -> Vec<u8> {
-    match validate(&input) {
-        Ok(_) => input,
-        Err(_) => {
-            let mut fixed = Vec::with_capacity(input.len());
-            let len = input.len();
-            let mut pos = 0;
-
-            while let Err((decode_err, err_pos)) = validate(&input[pos..len]) {
-                // err_pos is relative to the slice we pass to validate, but we're indexing input from the beginning
-                let err_pos = err_pos + pos;
-                fixed.extend_from_slice(&input[pos..err_pos]);
-                pos = err_pos;
-                match decode_err {
-                    DecodeErr::InvalidCodeUnit => {
-                        fixed.extend_from_slice(REPLACEMENT);
-                        pos += 1;
-                    }
-                    DecodeErr::IncompleteCharacter => {
-                        fixed.extend_from_slice(REPLACEMENT);
-                        let code_unit = CodeUnit::try_from(input[pos]).unwrap();
-                        let expected_continuations = &input[(pos + 1)..(pos + code_unit.len())];
-                        let end = expected_continuations
-                            .iter()
-                            .position(|c_u| CodeUnit::try_from(*c_u) != Ok(CodeUnit::Continuation))
-                            .unwrap();
-                        pos += 1 + end;
-                    }
-                    DecodeErr::InvalidCodePoint => {
-                        fixed.extend_from_slice(REPLACEMENT);
-                        let code_unit = CodeUnit::try_from(input[pos]).unwrap();
-                        pos += code_unit.len();
-                    }
-                    DecodeErr::OverlongEncoding => {
-                        fixed.extend_from_slice(REPLACEMENT);
-                        let code_unit = CodeUnit::try_from(input[pos]).unwrap();
-                        pos += code_unit.len();
-                    }
-                    DecodeErr::UnexpectedContinuation => {
-                        pos += 1;
-                    }
+// What fix changed: each range of the input that it replaced, and what was wrong with it.
+#[derive(Debug, Default, PartialEq)]
+pub struct RepairReport {
+    pub replaced: Vec<(DecodeErr, Range<usize>)>,
+}
+
+impl RepairReport {
+    pub fn is_clean(&self) -> bool {
+        self.replaced.is_empty()
+    }
+}
+
+// What fix_with puts in place of each invalid sequence.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplacementPolicy<'a> {
+    // U+FFFD REPLACEMENT CHARACTER.
+    Replace,
+    Drop,
+    Substitute(&'a str),
+    // Each byte as \xNN, so e.g. C0 80 becomes \xC0\x80. The bytes can be read off, but it can't
+    // be undone, since the input could already have had text like \xC0 in it.
+    Escape,
+}
+
+// Borrows the input if there's nothing to fix.
+pub fn fix(input: &[u8]) -> (Cow<'_, [u8]>, RepairReport) {
+    fix_with(input, ReplacementPolicy::Replace)
+}
+
+pub fn fix_with<'a>(input: &'a [u8], policy: ReplacementPolicy) -> (Cow<'a, [u8]>, RepairReport) {
+    let replaced = validate_all(input);
+    if replaced.is_empty() {
+        return (Cow::Borrowed(input), RepairReport::default());
+    }
+    // Overlong encodings are always replaced, never decoded, since the point of rejecting them is
+    // that e.g. C0 AF mustn't sneak a / past anything checking for one.
+    // Each maximal subpart is replaced separately, the way the Unicode standard and WHATWG
+    // recommend, so e.g. C0 80 is two replacement characters, but a character that's been cut
+    // short is one.
+    let mut fixed = Vec::with_capacity(input.len());
+    let mut pos = 0;
+    for (_, range) in &replaced {
+        fixed.extend_from_slice(&input[pos..range.start]);
+        match policy {
+            ReplacementPolicy::Replace => fixed.extend_from_slice(REPLACEMENT),
+            ReplacementPolicy::Drop => {}
+            ReplacementPolicy::Substitute(s) => fixed.extend_from_slice(s.as_bytes()),
+            ReplacementPolicy::Escape => {
+                for b in &input[range.clone()] {
+                    let [hi, lo] = [
+                        HEX_DIGITS[(b >> 4) as usize],
+                        HEX_DIGITS[(b & 0xF) as usize],
+                    ];
+                    fixed.extend_from_slice(&[b'\\', b'x', hi, lo]);
                 }
             }
-            fixed.extend_from_slice(&input[pos..len]);
-            return fixed;
         }
+        pos = range.end;
     }
+    fixed.extend_from_slice(&input[pos..]);
+    (Cow::Owned(fixed), RepairReport { replaced })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cp_iter::LossyCodePointIter;
 
     #[test]
     fn test_fix() {
-        assert_eq!(fix(vec![0xc0, 0x80]), REPLACEMENT);
-        assert_eq!(fix(vec![0xc0, 0xAE]), REPLACEMENT);
+        assert_eq!(fix(&[0xc0, 0x80]).0, "\u{FFFD}\u{FFFD}".as_bytes());
+        assert_eq!(fix(&[0xc0, 0xAE]).0, "\u{FFFD}\u{FFFD}".as_bytes());
         assert_eq!(
-            fix(vec![0xF0, 0x80, 0x80, 0x41]),
-            vec![0xEF, 0xBF, 0xBD, 0x41]
+            fix(&[0xF0, 0x80, 0x80, 0x41]).0,
+            "\u{FFFD}\u{FFFD}\u{FFFD}A".as_bytes()
         );
 
         let xs = b"hello".to_vec();
         let ys = "hello".as_bytes();
-        assert_eq!(fix(xs), ys);
+        assert_eq!(fix(&xs).0, ys);
 
         let xs = "ศไทย中华Việt Nam".as_bytes().to_vec();
         let ys = "ศไทย中华Việt Nam".as_bytes();
-        assert_eq!(fix(xs), ys);
+        assert_eq!(fix(&xs).0, ys);
 
         let xs = b"Hello\xC2 There\xFF Goodbye".to_vec();
         let ys = "Hello\u{FFFD} There\u{FFFD} Goodbye".as_bytes();
-        assert_eq!(fix(xs), ys);
-
-        // Most of these are replacing each error byte. I'm replacing the subsequence.
+        assert_eq!(fix(&xs).0, ys);
 
         let xs = b"Hello\xC0\x80 There\xE6\x83 Goodbye".to_vec();
-        // assert_eq!(fix(xs), "Hello\u{FFFD}\u{FFFD} There\u{FFFD} Goodbye".as_bytes());
-        assert_eq!(fix(xs), "Hello\u{FFFD} There\u{FFFD} Goodbye".as_bytes());
+        assert_eq!(
+            fix(&xs).0,
+            "Hello\u{FFFD}\u{FFFD} There\u{FFFD} Goodbye".as_bytes()
+        );
 
         let xs = b"\xF5foo\xF5\x80bar".to_vec();
-        // assert_eq!(fix(xs), "\u{FFFD}foo\u{FFFD}\u{FFFD}bar".as_bytes());
-        assert_eq!(fix(xs), "\u{FFFD}foo\u{FFFD}bar".as_bytes());
+        assert_eq!(fix(&xs).0, "\u{FFFD}foo\u{FFFD}\u{FFFD}bar".as_bytes());
 
         let xs = b"\xF1foo\xF1\x80bar\xF1\x80\x80baz".to_vec();
-        assert_eq!(fix(xs), "\u{FFFD}foo\u{FFFD}bar\u{FFFD}baz".as_bytes());
+        assert_eq!(fix(&xs).0, "\u{FFFD}foo\u{FFFD}bar\u{FFFD}baz".as_bytes());
 
         let xs = b"\xF4foo\xF4\x80bar\xF4\xBFbaz".to_vec();
-        // assert_eq!(fix(xs), "\u{FFFD}foo\u{FFFD}bar\u{FFFD}\u{FFFD}baz".as_bytes());
-        assert_eq!(fix(xs), "\u{FFFD}foo\u{FFFD}bar\u{FFFD}baz".as_bytes());
+        assert_eq!(
+            fix(&xs).0,
+            "\u{FFFD}foo\u{FFFD}bar\u{FFFD}\u{FFFD}baz".as_bytes()
+        );
 
         let xs = b"\xF0\x80\x80\x80foo\xF0\x90\x80\x80bar".to_vec();
-        // assert_eq!(fix(xs), "\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}foo\u{10000}bar".as_bytes());
-        assert_eq!(fix(xs), "\u{FFFD}foo\u{10000}bar".as_bytes());
+        assert_eq!(
+            fix(&xs).0,
+            "\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}foo\u{10000}bar".as_bytes()
+        );
 
-        // // surrogates
+        // surrogates
         let xs = b"\xED\xA0\x80foo\xED\xBF\xBFbar".to_vec();
-        // assert_eq!(fix(xs), "\u{FFFD}\u{FFFD}\u{FFFD}foo\u{FFFD}\u{FFFD}\u{FFFD}bar".as_bytes());
-        assert_eq!(fix(xs), "\u{FFFD}foo\u{FFFD}bar".as_bytes());
+        assert_eq!(
+            fix(&xs).0,
+            "\u{FFFD}\u{FFFD}\u{FFFD}foo\u{FFFD}\u{FFFD}\u{FFFD}bar".as_bytes()
+        );
+
+        // Cut off at the end.
+        assert_eq!(fix(b"ok\xF0\x9F\x98").0, "ok\u{FFFD}".as_bytes());
+    }
+
+    #[test]
+    fn test_repair_report() {
+        let (fixed, report) = fix("ศไทย".as_bytes());
+        assert!(matches!(fixed, Cow::Borrowed(_)));
+        assert!(report.is_clean());
+
+        let (fixed, report) = fix(b"a\xC0\x80b\xE2\x82");
+        assert_eq!(fixed, "a\u{FFFD}\u{FFFD}b\u{FFFD}".as_bytes());
+        assert!(!report.is_clean());
+        assert_eq!(
+            report.replaced,
+            [
+                (DecodeErr::OverlongEncoding, 1..2),
+                (DecodeErr::UnexpectedContinuation, 2..3),
+                (DecodeErr::IncompleteCharacter, 4..6),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fix_with() {
+        let input = b"a\xC0\x80b\xE2\x82";
+        let fixed = |policy| fix_with(input, policy).0.into_owned();
+        assert_eq!(
+            fixed(ReplacementPolicy::Replace),
+            "a\u{FFFD}\u{FFFD}b\u{FFFD}".as_bytes()
+        );
+        assert_eq!(fixed(ReplacementPolicy::Drop), b"ab");
+        assert_eq!(fixed(ReplacementPolicy::Substitute("?")), b"a??b?");
+        assert_eq!(fixed(ReplacementPolicy::Substitute("")), b"ab");
+        assert_eq!(fixed(ReplacementPolicy::Escape), b"a\\xC0\\x80b\\xE2\\x82");
+        assert_eq!(
+            fix_with(b"\xFF\x9A\x0A", ReplacementPolicy::Escape).0,
+            &b"\\xFF\\x9A\n"[..]
+        );
+        // The report is the same whatever they were replaced with.
+        assert_eq!(fix_with(input, ReplacementPolicy::Drop).1, fix(input).1);
+        assert!(matches!(
+            fix_with(b"fine", ReplacementPolicy::Escape).0,
+            Cow::Borrowed(b"fine")
+        ));
+    }
+
+    #[test]
+    fn test_overlongs_are_not_decoded() {
+        // NUL, / and . as overlongs.
+        let inputs: [&[u8]; 4] = [
+            b"\xC0\x80",
+            b"\xC0\xAF",
+            b"\xE0\x80\xAF",
+            b"\xF0\x80\x80\xAE",
+        ];
+        for input in inputs {
+            let (fixed, report) = fix(input);
+            assert!(fixed.iter().all(|b| !matches!(b, 0x00 | b'/' | b'.')));
+            assert_eq!(report.replaced[0].0, DecodeErr::OverlongEncoding);
+            let lossy: Vec<u32> = LossyCodePointIter::new(input).collect();
+            assert!(lossy.iter().all(|cp| *cp == 0xFFFD));
+        }
     }
 }
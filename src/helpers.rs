@@ -1,3 +1,6 @@
+use std::cmp::min;
+use std::io;
+
 #[derive(Debug, PartialEq)]
 pub enum CodeUnit {
     SingleByte,
@@ -46,8 +49,8 @@ impl TryFrom<u8> for CodeUnit {
 pub fn is_valid_codepoint(code_point: u32) -> bool {
     // todo: non-characters
     let below_max_code_point = code_point <= 0x10FFFF;
-    let not_half_of_utf16_surrogate_pair = code_point < 0xD800 || code_point > 0xDFFF;
-    return below_max_code_point && not_half_of_utf16_surrogate_pair;
+    let not_half_of_utf16_surrogate_pair = !(0xD800..=0xDFFF).contains(&code_point);
+    below_max_code_point && not_half_of_utf16_surrogate_pair
 }
 
 const CLEAR_12: u8 = 0b0011_1111;
@@ -62,14 +65,14 @@ pub fn decode_double(first: u8, second: u8) -> u32 {
     // 110a_aaaa 10bb_bbbb -> 0000_0aaa aabb_bbbb
     let high_byte = first >> 2 & CLEAR_12345;
     let low_byte = (first << 6 & CLEAR_345678) | (second & CLEAR_12);
-    return u32::from_be_bytes([0, 0, high_byte, low_byte]);
+    u32::from_be_bytes([0, 0, high_byte, low_byte])
 }
 
 pub fn decode_triple(first: u8, second: u8, third: u8) -> u32 {
     // 1110_aaaa 10bb_bbbb 10cc_cccc -> aaaa_bbbb bbcc_cccc
     let high_byte = (first << 4 & CLEAR_5678) | (second >> 2 & CLEAR_1234);
     let low_byte = (second << 6 & CLEAR_345678) | (third & CLEAR_12);
-    return u32::from_be_bytes([0, 0, high_byte, low_byte]);
+    u32::from_be_bytes([0, 0, high_byte, low_byte])
 }
 
 pub fn decode_quad(first: u8, second: u8, third: u8, fourth: u8) -> u32 {
@@ -77,35 +80,157 @@ pub fn decode_quad(first: u8, second: u8, third: u8, fourth: u8) -> u32 {
     let high_byte = (first << 2 & CLEAR_12378) | (second >> 4 & CLEAR_123456);
     let middle_byte = (second << 4 & CLEAR_5678) | (third >> 2 & CLEAR_1234);
     let low_byte = (third << 6 & CLEAR_345678) | (fourth & CLEAR_12);
-    return u32::from_be_bytes([0, high_byte, middle_byte, low_byte]);
+    u32::from_be_bytes([0, high_byte, middle_byte, low_byte])
+}
+
+// How many bytes at the start are ASCII. It checks a word at a time, since if none of the eight
+// bytes have their high bit set, they're all ASCII, and only goes byte by byte at the end.
+pub fn ascii_prefix_len(bytes: &[u8]) -> usize {
+    let mut pos = 0;
+    for chunk in bytes.chunks_exact(8) {
+        let word = u64::from_ne_bytes(chunk.try_into().unwrap());
+        if word & 0x8080_8080_8080_8080 != 0 {
+            break;
+        }
+        pos += 8;
+    }
+    pos + bytes[pos..].iter().take_while(|b| b.is_ascii()).count()
+}
+
+// How many bytes there are before an incomplete character at the end, if there is one. The end is
+// only held back if more bytes could still make it well-formed, so something like ED A0, which is
+// already a surrogate, or C0, which can't start anything, is left in to be reported straight away.
+pub fn complete_len(bytes: &[u8]) -> usize {
+    for back in 1..=min(3, bytes.len()) {
+        let pos = bytes.len() - back;
+        match CodeUnit::try_from(bytes[pos]) {
+            Ok(CodeUnit::Continuation) => continue,
+            Ok(code_unit)
+                if code_unit.len() > back
+                    && (0xC2..=0xF4).contains(&bytes[pos])
+                    && maximal_subpart_len(bytes, pos) == back =>
+            {
+                return pos
+            }
+            _ => break,
+        }
+    }
+    bytes.len()
+}
+
+// The maximal subpart of an invalid sequence at pos: as much of the start of a well-formed one as
+// there is, or just the first byte if it couldn't start one. So C0 80 is two subparts, since no
+// well-formed sequence starts with C0, but E2 82 followed by something else is one.
+// https://www.unicode.org/versions/Unicode14.0.0/ch03.pdf, table 3-7
+pub fn maximal_subpart_len(input: &[u8], pos: usize) -> usize {
+    let (len, second) = match input[pos] {
+        0xC2..=0xDF => (2, 0x80..=0xBF),
+        0xE0 => (3, 0xA0..=0xBF),
+        0xE1..=0xEC | 0xEE..=0xEF => (3, 0x80..=0xBF),
+        0xED => (3, 0x80..=0x9F),
+        0xF0 => (4, 0x90..=0xBF),
+        0xF1..=0xF3 => (4, 0x80..=0xBF),
+        0xF4 => (4, 0x80..=0x8F),
+        _ => return 1,
+    };
+    let mut n = 1;
+    while n < len && pos + n < input.len() {
+        let expected = if n == 1 { second.clone() } else { 0x80..=0xBF };
+        if !expected.contains(&input[pos + n]) {
+            break;
+        }
+        n += 1;
+    }
+    n
 }
 
+pub fn invalid_utf8(err: DecodeErr, pos: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{:?} at byte {}", err, pos),
+    )
+}
+
+// The library encodes with encode_utf8_into, so only the tests need a Vec per code point.
+#[cfg(test)]
 pub fn encode_utf8(code_point: u32) -> Vec<u8> {
-    let bytes = code_point.to_be_bytes();
+    let mut buf = [0; 4];
+    let len = encode_utf8_to(code_point, &mut buf);
+    buf[..len].to_vec()
+}
+
+// Appends the encoding to out, without allocating for each code point.
+pub fn encode_utf8_into(code_point: u32, out: &mut Vec<u8>) {
+    let mut buf = [0; 4];
+    let len = encode_utf8_to(code_point, &mut buf);
+    out.extend_from_slice(&buf[..len]);
+}
+
+// Writes the encoding to the start of buf, and returns how many bytes it took. Anything above
+// U+10FFFF can't be encoded, so it's written as U+FFFD instead, the same as fix does with bytes
+// that can't be decoded. Surrogates are encoded like any other three byte code point, for WTF-8.
+pub fn encode_utf8_to(code_point: u32, buf: &mut [u8; 4]) -> usize {
+    let [_, top, hi, lo] = code_point.to_be_bytes();
     match code_point {
-        0..=0x007F => vec![code_point as u8],
+        0..=0x007F => {
+            buf[0] = code_point as u8;
+            1
+        }
         0x0080..=0x07FF => {
             // 0000_0aaa aabb_bbbb -> 110a_aaaa 10bb_bbbb
-            let first_byte = 0b1100_0000 | (bytes[0] << 2) | (bytes[1] >> 6);
-            let second_byte = bytes[1] | 0b1000_0000 & 0b1011_1111;
-            vec![first_byte, second_byte]
+            buf[0] = 0b1100_0000 | (hi << 2) | (lo >> 6);
+            buf[1] = 0b1000_0000 | (lo & 0b0011_1111);
+            2
         }
         0x0800..=0xFFFF => {
             // aaaa_bbbb bbcc_cccc -> 1110_aaaa 10bb_bbbb 10cc_cccc
-            let first_byte = 0b1110_0000 | (bytes[0] >> 4);
-            let second_byte = 0b1000_0000 | (bytes[0] << 2 & 0b00111100) | (bytes[1] >> 6);
-            let third_byte = 0b1000_0000 | (bytes[1] & 0b0011_1111);
-            vec![first_byte, second_byte, third_byte]
+            buf[0] = 0b1110_0000 | (hi >> 4);
+            buf[1] = 0b1000_0000 | (hi << 2 & 0b00111100) | (lo >> 6);
+            buf[2] = 0b1000_0000 | (lo & 0b0011_1111);
+            3
+        }
+        0x10000..=0x10FFFF => {
+            // 000a_aabb bbbb_cccc ccdd_dddd -> 1111_0aaa 10bb_bbbb 10cc_cccc 10dd_dddd
+            buf[0] = 0b1111_0000 | (top >> 2);
+            buf[1] = 0b1000_0000 | (top << 4 & 0b0011_0000) | (hi >> 4);
+            buf[2] = 0b1000_0000 | (hi << 2 & 0b0011_1100) | (lo >> 6);
+            buf[3] = 0b1000_0000 | (lo & 0b0011_1111);
+            4
         }
-        0x10000..=0x10FFFF => todo!(),
-        _ => unimplemented!(),
+        _ => encode_utf8_to(0xFFFD, buf),
+    }
+}
+
+// FNV-1a, for the hashes that have to be the same on every machine and between releases, which
+// std's hasher doesn't promise. http://www.isthe.com/chongo/tech/comp/fnv/
+pub fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
     }
+    hash
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a([]), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(*b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(*b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn test_ascii_prefix_len() {
+        assert_eq!(ascii_prefix_len(b""), 0);
+        assert_eq!(ascii_prefix_len(b"abc"), 3);
+        assert_eq!(ascii_prefix_len("abcdefghijklmn\u{00E5}".as_bytes()), 14);
+        assert_eq!(ascii_prefix_len("abc\u{00E5}defghijklmnop".as_bytes()), 3);
+    }
+
     #[test]
     fn test_codepoint_from_u8() {
         assert_eq!(CodeUnit::try_from(0), Ok(CodeUnit::SingleByte));
@@ -134,10 +259,10 @@ mod tests {
         assert!(is_valid_codepoint(0x20AC));
         assert!(is_valid_codepoint(0xD55C));
         assert!(is_valid_codepoint(0x10348));
-        assert_eq!(is_valid_codepoint(0x110000), false);
-        assert_eq!(is_valid_codepoint(0xD800), false);
-        assert_eq!(is_valid_codepoint(0xDABC), false);
-        assert_eq!(is_valid_codepoint(0xDFFF), false);
+        assert!(!is_valid_codepoint(0x110000));
+        assert!(!is_valid_codepoint(0xD800));
+        assert!(!is_valid_codepoint(0xDABC));
+        assert!(!is_valid_codepoint(0xDFFF));
     }
 
     #[test]
@@ -160,4 +285,73 @@ mod tests {
             0x10348
         );
     }
+
+    #[test]
+    fn test_encode_utf8() {
+        assert_eq!(encode_utf8(0x24), [0x24]);
+        assert_eq!(encode_utf8(0xA3), [0xC2, 0xA3]);
+        assert_eq!(encode_utf8(0x20AC), [0xE2, 0x82, 0xAC]);
+        assert_eq!(encode_utf8(0x10348), [0xF0, 0x90, 0x8D, 0x88]);
+        assert_eq!(encode_utf8(0x1F600), [0xF0, 0x9F, 0x98, 0x80]);
+        assert_eq!(encode_utf8(0x10FFFF), [0xF4, 0x8F, 0xBF, 0xBF]);
+        // The same as the standard library, for every scalar value.
+        for c in (0..=0x10FFFF).filter_map(char::from_u32) {
+            let mut buf = [0; 4];
+            assert_eq!(encode_utf8(c as u32), c.encode_utf8(&mut buf).as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_encode_utf8_round_trip() {
+        for code_point in 0x10000..=0x10FFFF {
+            let [first, second, third, fourth] = encode_utf8(code_point)[..] else {
+                panic!("{:X} should be four bytes", code_point);
+            };
+            assert_eq!(decode_quad(first, second, third, fourth), code_point);
+        }
+        for code_point in 0x0800..=0xFFFF {
+            let [first, second, third] = encode_utf8(code_point)[..] else {
+                panic!("{:X} should be three bytes", code_point);
+            };
+            assert_eq!(decode_triple(first, second, third), code_point);
+        }
+    }
+
+    #[test]
+    fn test_encode_utf8_to() {
+        let mut buf = [0; 4];
+        assert_eq!(encode_utf8_to(0x24, &mut buf), 1);
+        assert_eq!(buf[..1], [0x24]);
+        assert_eq!(encode_utf8_to(0x1F600, &mut buf), 4);
+        assert_eq!(buf, [0xF0, 0x9F, 0x98, 0x80]);
+        // Only the start is written.
+        assert_eq!(encode_utf8_to(0xA3, &mut buf), 2);
+        assert_eq!(buf, [0xC2, 0xA3, 0x98, 0x80]);
+
+        assert_eq!(encode_utf8_to(0x110000, &mut buf), 3);
+        assert_eq!(buf[..3], [0xEF, 0xBF, 0xBD]);
+        assert_eq!(encode_utf8(u32::MAX), "\u{FFFD}".as_bytes());
+
+        let mut out = b"x".to_vec();
+        for cp in [0x20AC, 0x61, 0x10348] {
+            encode_utf8_into(cp, &mut out);
+        }
+        assert_eq!(out, "x\u{20AC}a\u{10348}".as_bytes());
+    }
+
+    #[test]
+    fn test_maximal_subpart_len() {
+        assert_eq!(maximal_subpart_len(b"\x80", 0), 1);
+        assert_eq!(maximal_subpart_len(b"\xC0\x80", 0), 1);
+        assert_eq!(maximal_subpart_len(b"\xFF", 0), 1);
+        assert_eq!(maximal_subpart_len(b"\xE2\x82x", 0), 2);
+        assert_eq!(maximal_subpart_len(b"\xE2\x82", 0), 2);
+        assert_eq!(maximal_subpart_len(b"\xF0\x9F\x98", 0), 3);
+        assert_eq!(maximal_subpart_len(b"x\xF0\x9F\x98x", 1), 3);
+        // Overlong, surrogate, and too high, caught at the second byte.
+        assert_eq!(maximal_subpart_len(b"\xE0\x80\x80", 0), 1);
+        assert_eq!(maximal_subpart_len(b"\xED\xA0\x80", 0), 1);
+        assert_eq!(maximal_subpart_len(b"\xF4\x90\x80\x80", 0), 1);
+        assert_eq!(maximal_subpart_len(b"\xF4\x8F\xBFx", 0), 3);
+    }
 }
@@ -0,0 +1,61 @@
+// Converting from the single byte encodings that text which isn't UTF-8 is most often really in.
+// Every byte is a character in both of them, so unlike fix, nothing needs replacing.
+
+use crate::helpers::encode_utf8_into;
+
+// https://www.unicode.org/Public/MAPPINGS/VENDORS/MICSFT/WINDOWS/CP1252.TXT
+// 0x80 to 0x9F, where Windows-1252 has punctuation and letters instead of the C1 controls. The
+// five bytes it leaves undefined stay as the controls, the way browsers decode them.
+const WINDOWS_1252_HIGH: [u32; 32] = [
+    0x20AC, 0x0081, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0x008D, 0x017D, 0x008F, 0x0090, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0x009D, 0x017E, 0x0178,
+];
+
+// Latin-1's bytes are the first 256 code points.
+pub fn latin1_to_utf8(bytes: &[u8]) -> String {
+    transcode(bytes, |b| b as u32)
+}
+
+pub fn windows_1252_to_utf8(bytes: &[u8]) -> String {
+    transcode(bytes, |b| match b {
+        0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+        _ => b as u32,
+    })
+}
+
+fn transcode(bytes: &[u8], to_code_point: impl Fn(u8) -> u32) -> String {
+    let mut out = Vec::with_capacity(bytes.len());
+    for b in bytes {
+        encode_utf8_into(to_code_point(*b), &mut out);
+    }
+    String::from_utf8(out).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latin1_to_utf8() {
+        assert_eq!(latin1_to_utf8(b"plain"), "plain");
+        assert_eq!(latin1_to_utf8(b"caf\xE9 \xA35 \xFF"), "café £5 ÿ");
+        assert_eq!(latin1_to_utf8(b"\x80\x9F"), "\u{80}\u{9F}");
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(latin1_to_utf8(&all).chars().count(), 256);
+    }
+
+    #[test]
+    fn test_windows_1252_to_utf8() {
+        assert_eq!(
+            windows_1252_to_utf8(b"\x93quoted\x94 \x96 \x80100 \x85"),
+            "\u{201C}quoted\u{201D} \u{2013} €100 …"
+        );
+        assert_eq!(windows_1252_to_utf8(b"caf\xE9"), "café");
+        assert_eq!(windows_1252_to_utf8(b"\x8A\x9F"), "ŠŸ");
+        assert_eq!(
+            windows_1252_to_utf8(b"\x81\x8D\x8F\x90\x9D"),
+            "\u{81}\u{8D}\u{8F}\u{90}\u{9D}"
+        );
+    }
+}
@@ -1,10 +1,21 @@
+pub mod bidi;
 pub mod case;
+pub mod chars;
 pub mod collation;
 mod cp_iter;
 pub mod fix;
 mod helpers;
+pub mod legacy;
 pub mod normalise;
 pub mod segmentation;
 mod trie;
 mod ucd;
+pub mod utf16;
+pub mod utf32;
 mod validate;
+pub mod wtf8;
+
+pub use cp_iter::{LossyCodePointIter, TryCodePointIter};
+pub use helpers::DecodeErr;
+pub use ucd::{canonical_composition, is_composition_exclusion, titlecase_mapping};
+pub use validate::{decode_utf8, validate_all, Utf8Validator};
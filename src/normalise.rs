@@ -1,10 +1,19 @@
-use crate::cp_iter::CodePointIter;
-use crate::helpers::encode_utf8;
+use crate::cp_iter::{CodePointIter, TryCodePointIter};
+use crate::helpers::{
+    ascii_prefix_len, complete_len, encode_utf8_into, invalid_utf8, is_valid_codepoint, DecodeErr,
+};
+pub use crate::ucd::DecompositionType;
 use crate::ucd::{
-    combining_class, decomposition_mapping, is_allowed, is_starter, primary_composite,
-    QuickCheckVal,
+    canonical_composition, combining_class, compatibility_decomposition_mapping,
+    decomposition_mapping, decomposition_mapping_slice, decomposition_type, is_allowed,
+    is_assigned, is_starter, QuickCheckVal,
 };
+use crate::validate::decode_utf8;
+use std::borrow::Cow;
 use std::cmp::min;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::ops::Deref;
 
 // https://www.unicode.org/reports/tr15/#Detecting_Normalization_Forms
 
@@ -15,45 +24,276 @@ pub enum IsNormalised {
     Maybe,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Normalisation {
     NFC,
     NFD,
-    // skipping these for now, I'm not that interested in the compatability equivalence,
-    // it would make more sense if one was doing search
-    // NFKC,
-    // NFKD,
+    NFKC,
+    NFKD,
+}
+
+pub fn quick_check(code_points: &[u32], normalisation: Normalisation) -> IsNormalised {
+    quick_check_position(code_points, normalisation).0
+}
+
+// Same as quick_check, but also gives the index of the first code point that wasn't a Yes.
+// Everything before that is fine, so a caller only needs to renormalise from the last starter
+// before it.
+pub fn quick_check_position(
+    code_points: &[u32],
+    normalisation: Normalisation,
+) -> (IsNormalised, Option<usize>) {
+    quick_check_iter(code_points.iter().copied(), normalisation)
 }
 
-pub fn quick_check(code_points: &Vec<u32>, normalisation: Normalisation) -> IsNormalised {
+// Checks UTF-8 without decoding it all into a Vec<u32> first. ASCII is Yes for every form, and
+// has a combining class of 0, so it can skip ahead to the first byte that isn't ASCII and start
+// checking from there. The rest is decoded and checked in the same pass, so it stops at the first
+// No, and doesn't look at the bytes after it. Errors before that come with the byte offset
+// they're at, like validate.
+pub fn quick_check_utf8(
+    bytes: &[u8],
+    normalisation: Normalisation,
+) -> Result<IsNormalised, (DecodeErr, usize)> {
+    let ascii = ascii_prefix_len(bytes);
+    if ascii == bytes.len() {
+        return Ok(IsNormalised::Yes);
+    }
+    let mut code_points = TryCodePointIter::new(&bytes[ascii..]);
+    let mut error = None;
+    let decoded = std::iter::from_fn(|| {
+        let pos = code_points.position();
+        match code_points.next()? {
+            Ok(code_point) => Some(code_point),
+            Err((err, _)) => {
+                error = Some((err, ascii + pos));
+                None
+            }
+        }
+    });
+    let (result, _) = quick_check_iter(decoded, normalisation);
+    match error {
+        Some(error) => Err(error),
+        None => Ok(result),
+    }
+}
+
+fn quick_check_iter(
+    code_points: impl Iterator<Item = u32>,
+    normalisation: Normalisation,
+) -> (IsNormalised, Option<usize>) {
     let mut last_canonical_class: u8 = 0;
     let mut result: IsNormalised = IsNormalised::Yes;
-    for code_point in code_points.into_iter() {
-        let ccc = combining_class(*code_point);
+    let mut position = None;
+    for (i, code_point) in code_points.enumerate() {
+        let ccc = combining_class(code_point);
         if last_canonical_class > ccc && ccc != 0 {
-            return IsNormalised::No;
+            return (IsNormalised::No, position.or(Some(i)));
         }
-        match is_allowed(*code_point, &normalisation) {
+        match is_allowed(code_point, &normalisation) {
             QuickCheckVal::Yes => {}
             QuickCheckVal::No => {
-                return IsNormalised::No;
+                return (IsNormalised::No, position.or(Some(i)));
             }
             QuickCheckVal::Maybe => {
                 result = IsNormalised::Maybe;
+                position = position.or(Some(i));
             }
         }
         last_canonical_class = ccc;
     }
 
-    return result;
+    (result, position)
+}
+
+// Quick check only gives up on the code points that can combine with something before them, so
+// rather than normalising everything, just normalise the stretch between the safe starters on
+// either side of each Maybe, and see whether it changes.
+pub fn is_nfc(code_points: &[u32]) -> bool {
+    is_composed(code_points, Normalisation::NFC, to_nfc)
+}
+
+// The NFKC Maybes are the same as the NFC ones, and if there are any No's it doesn't get as far as
+// looking at the spans, so the same boundaries work.
+pub fn is_nfkc(code_points: &[u32]) -> bool {
+    is_composed(code_points, Normalisation::NFKC, to_nfkc)
+}
+
+fn is_composed(
+    code_points: &[u32],
+    normalisation: Normalisation,
+    normalise: fn(&[u32]) -> Vec<u32>,
+) -> bool {
+    match quick_check(code_points, normalisation) {
+        IsNormalised::Yes => true,
+        IsNormalised::No => false,
+        IsNormalised::Maybe => {
+            let mut start = 0;
+            let mut i = 0;
+            while i < code_points.len() {
+                if is_composition_boundary(code_points[i]) {
+                    start = i;
+                } else if let QuickCheckVal::Maybe = is_allowed(code_points[i], &normalisation) {
+                    let end = next_boundary(code_points, i);
+                    let span = &code_points[start..end];
+                    if normalise(span) != span {
+                        return false;
+                    }
+                    start = end;
+                    i = end;
+                    continue;
+                }
+                i += 1;
+            }
+            true
+        }
+    }
+}
+
+// A starter that is NFC_QC=Yes can't combine with anything before it, and nothing before it can
+// be reordered past it, so normalisation never has to look back across one. Text can be split
+// before any of these and each part normalised separately.
+pub fn is_composition_boundary(cp: u32) -> bool {
+    is_starter(cp) && matches!(is_allowed(cp, &Normalisation::NFC), QuickCheckVal::Yes)
+}
+
+// The index of the next boundary after pos, or the end if there aren't any more.
+pub fn next_boundary(code_points: &[u32], pos: usize) -> usize {
+    code_points
+        .iter()
+        .skip(pos + 1)
+        .position(|cp| is_composition_boundary(*cp))
+        .map(|offset| pos + 1 + offset)
+        .unwrap_or(code_points.len())
+}
+
+// There's no Maybe for NFD, so the quick check is already the whole answer.
+pub fn is_nfd(code_points: &[u32]) -> bool {
+    quick_check(code_points, Normalisation::NFD) == IsNormalised::Yes
+}
+
+pub fn is_nfkd(code_points: &[u32]) -> bool {
+    quick_check(code_points, Normalisation::NFKD) == IsNormalised::Yes
+}
+
+// Which of the forms a string is already in, as a set of flags. ASCII is in all of them, and it's
+// common for text to be in both NFC and NFD, when there's nothing to compose.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NormalisationForms(u8);
+
+impl NormalisationForms {
+    pub fn contains(&self, normalisation: Normalisation) -> bool {
+        self.0 & NormalisationForms::flag(normalisation) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    fn insert(&mut self, normalisation: Normalisation) {
+        self.0 |= NormalisationForms::flag(normalisation);
+    }
+
+    fn flag(normalisation: Normalisation) -> u8 {
+        match normalisation {
+            Normalisation::NFC => 0b0001,
+            Normalisation::NFD => 0b0010,
+            Normalisation::NFKC => 0b0100,
+            Normalisation::NFKD => 0b1000,
+        }
+    }
+}
+
+pub fn detect_forms(code_points: &[u32]) -> NormalisationForms {
+    let mut forms = NormalisationForms::default();
+    if is_nfc(code_points) {
+        forms.insert(Normalisation::NFC);
+    }
+    if is_nfd(code_points) {
+        forms.insert(Normalisation::NFD);
+    }
+    if is_nfkc(code_points) {
+        forms.insert(Normalisation::NFKC);
+    }
+    if is_nfkd(code_points) {
+        forms.insert(Normalisation::NFKD);
+    }
+    forms
 }
 
 // These functions are inefficient in that each one iterates through the entire string and each
 // intermediate step produces a separate vector. They are done this way to make it easier to see
 // how the different stages build on each other, and because it's easier to test.
 // In a real application, we could easily skip a few of the intermediate vectors.
-pub fn to_nfc_str(bytes: Vec<u8>) -> Vec<u8> {
-    let cps: Vec<u32> = CodePointIter::new(bytes).collect();
-    to_nfc(&cps).into_iter().flat_map(encode_utf8).collect()
+
+// Most text is already in NFC, so if the quick check can tell, the input is given back as it is.
+// The quick check runs on the bytes, so they're only decoded when something might change. It stops
+// at the first No without validating the rest, so they're decoded with decode_utf8 rather than
+// trusted, and errors are the same as validate's either way.
+pub fn to_nfc_str(bytes: &[u8]) -> Result<Cow<'_, [u8]>, (DecodeErr, usize)> {
+    match quick_check_utf8(bytes, Normalisation::NFC)? {
+        IsNormalised::Yes => Ok(Cow::Borrowed(bytes)),
+        _ => {
+            let cps = decode_utf8(bytes)?;
+            Ok(Cow::Owned(to_utf8(to_nfc(&cps), bytes.len())))
+        }
+    }
+}
+
+pub fn to_nfd_str(bytes: &[u8]) -> Result<Cow<'_, [u8]>, (DecodeErr, usize)> {
+    match quick_check_utf8(bytes, Normalisation::NFD)? {
+        IsNormalised::Yes => Ok(Cow::Borrowed(bytes)),
+        _ => {
+            let cps = decode_utf8(bytes)?;
+            Ok(Cow::Owned(to_utf8(to_nfd(&cps), bytes.len())))
+        }
+    }
+}
+
+fn to_utf8(code_points: Vec<u32>, capacity: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(capacity);
+    for cp in code_points {
+        encode_utf8_into(cp, &mut out);
+    }
+    out
+}
+
+// Unassigned code points are passed through unchanged, but a later version of Unicode might give
+// them a decomposition or a combining class, and then the same input would normalise differently.
+// The strict version refuses them instead, along with anything that isn't a code point at all.
+#[derive(Debug, PartialEq)]
+pub enum NormalisationErr {
+    // A surrogate, or above U+10FFFF.
+    InvalidCodePoint,
+    Unassigned,
+}
+
+pub fn try_to_nfc(code_points: &[u32]) -> Result<Vec<u32>, (NormalisationErr, usize)> {
+    for (pos, cp) in code_points.iter().enumerate() {
+        if !is_valid_codepoint(*cp) {
+            return Err((NormalisationErr::InvalidCodePoint, pos));
+        }
+        if !is_assigned(*cp) {
+            return Err((NormalisationErr::Unassigned, pos));
+        }
+    }
+    Ok(to_nfc(code_points))
+}
+
+// Nothing below U+00C0 has a canonical decomposition or a non-zero combining class, and nothing
+// below U+0300 is changed by composition, other than by something after it combining with it. So
+// runs of those can be copied straight through without looking anything up, which is most text.
+const NFD_FAST_PATH_LIMIT: u32 = 0x00C0;
+const NFC_FAST_PATH_LIMIT: u32 = 0x0300;
+
+// The main entry points for normalising text. Everything else works on code points, these
+// take and give back strings.
+pub fn nfc(s: &str) -> String {
+    String::from_utf8(to_nfc_str(s.as_bytes()).unwrap().into_owned()).unwrap()
+}
+
+pub fn nfd(s: &str) -> String {
+    String::from_utf8(to_nfd_str(s.as_bytes()).unwrap().into_owned()).unwrap()
 }
 
 // The important bit here is that decompose is recursive.
@@ -64,13 +304,112 @@ pub fn decompose(cp: u32) -> Vec<u32> {
     }
 }
 
-// Decompose and canonically order the code points. Canonical ordering needs to use a stable sort,
-// which luckily Rust's default sort is.
-pub fn to_nfd(code_points: &Vec<u32>) -> Vec<u32> {
-    let mut decomposed: Vec<u32> = code_points.into_iter().fold(Vec::new(), |mut acc, cp| {
-        acc.extend(decompose(*cp));
+// Compatibility decomposition is also recursive, and at each step it uses whichever mapping the
+// code point has. No code point has both, the compatibility mappings are the ones in the UCD with
+// a type tag, like <font> or <super>.
+pub fn decompose_compatibility(cp: u32) -> Vec<u32> {
+    match decomposition_mapping(cp).or_else(|| compatibility_decomposition_mapping(cp)) {
+        None => vec![cp],
+        Some(dm) => dm.into_iter().flat_map(decompose_compatibility).collect(),
+    }
+}
+
+// A single step of compatibility decomposition along with its tag, which makes it possible to only
+// apply some kinds, like folding <wide> but leaving <fraction> alone. It isn't recursive, because
+// the steps can have different tags.
+pub fn decompose_compat(cp: u32) -> Option<(DecompositionType, Vec<u32>)> {
+    Some((
+        decomposition_type(cp)?,
+        compatibility_decomposition_mapping(cp)?,
+    ))
+}
+
+// Decompose and canonically order the code points.
+pub fn to_nfd(code_points: &[u32]) -> Vec<u32> {
+    let mut decomposed = Vec::with_capacity(code_points.len());
+    to_nfd_into(code_points, &mut decomposed);
+    decomposed
+}
+
+// The same as to_nfd and to_nfc, but writing into a buffer the caller owns, so that calling them
+// in a loop doesn't allocate every time. Decomposition pushes straight onto the buffer, borrowing
+// each mapping from the table rather than cloning it, and composition happens in the buffer too.
+pub fn to_nfd_into(code_points: &[u32], buf: &mut Vec<u32>) {
+    buf.clear();
+    let mut pos = 0;
+    while pos < code_points.len() {
+        let fast = code_points[pos..]
+            .iter()
+            .take_while(|cp| **cp < NFD_FAST_PATH_LIMIT)
+            .count();
+        buf.extend_from_slice(&code_points[pos..pos + fast]);
+        pos += fast;
+
+        let slow = code_points[pos..]
+            .iter()
+            .take_while(|cp| **cp >= NFD_FAST_PATH_LIMIT)
+            .count();
+        let start = buf.len();
+        for cp in &code_points[pos..pos + slow] {
+            decompose_into(*cp, buf);
+        }
+        canonical_reorder(&mut buf[start..]);
+        pos += slow;
+    }
+}
+
+pub fn to_nfc_into(code_points: &[u32], buf: &mut Vec<u32>) {
+    buf.clear();
+    let mut pos = 0;
+    while pos < code_points.len() {
+        let fast = code_points[pos..]
+            .iter()
+            .take_while(|cp| **cp < NFC_FAST_PATH_LIMIT)
+            .count();
+        if pos + fast == code_points.len() {
+            buf.extend_from_slice(&code_points[pos..]);
+            break;
+        }
+        // The last one might still combine with what comes after it.
+        let copy = fast.saturating_sub(1);
+        buf.extend_from_slice(&code_points[pos..pos + copy]);
+        pos += copy;
+
+        let end = code_points[pos + 1..]
+            .iter()
+            .position(|cp| *cp < NFC_FAST_PATH_LIMIT)
+            .map(|offset| pos + 1 + offset)
+            .unwrap_or(code_points.len());
+        let start = buf.len();
+        for cp in &code_points[pos..end] {
+            decompose_into(*cp, buf);
+        }
+        canonical_reorder(&mut buf[start..]);
+        compose_from(buf, start);
+        pos = end;
+    }
+}
+
+fn decompose_into(cp: u32, buf: &mut Vec<u32>) {
+    match decomposition_mapping_slice(cp) {
+        None => buf.push(cp),
+        Some(dm) => dm.iter().for_each(|c| decompose_into(*c, buf)),
+    }
+}
+
+pub fn to_nfkd(code_points: &[u32]) -> Vec<u32> {
+    let mut decomposed: Vec<u32> = code_points.iter().fold(Vec::new(), |mut acc, cp| {
+        acc.extend(decompose_compatibility(*cp));
         acc
     });
+    canonical_reorder(&mut decomposed);
+    decomposed
+}
+
+// Canonical ordering needs to use a stable sort, which luckily Rust's default sort is. Each run of
+// non-starters is sorted by combining class, and starters stay where they are. It doesn't need the
+// input to be decomposed, but unless it is, the result isn't any normalisation form.
+pub fn canonical_reorder(decomposed: &mut [u32]) {
     let mut pos = 0;
     while pos < decomposed.len() {
         let next_starter_offset = decomposed[pos..]
@@ -79,22 +418,46 @@ pub fn to_nfd(code_points: &Vec<u32>) -> Vec<u32> {
             .position(|cp| is_starter(*cp))
             .map(|offset| offset + 1)
             .unwrap_or(decomposed.len() - pos);
-        decomposed[pos..(pos + next_starter_offset)]
-            .sort_by(|a, b| combining_class(*a).cmp(&combining_class(*b)));
+        decomposed[pos..(pos + next_starter_offset)].sort_by_key(|a| combining_class(*a));
         pos += next_starter_offset
     }
-    decomposed
 }
 
-fn to_nfc(code_points: &Vec<u32>) -> Vec<u32> {
-    let mut nfd = to_nfd(code_points);
-    let mut pos = 0;
+pub fn to_nfc(code_points: &[u32]) -> Vec<u32> {
+    let mut composed = Vec::with_capacity(code_points.len());
+    to_nfc_into(code_points, &mut composed);
+    composed
+}
+
+// NFKC is the same composition, it just starts from the compatibility decomposition.
+pub fn to_nfkc(code_points: &[u32]) -> Vec<u32> {
+    let mut nfkd = to_nfkd(code_points);
+    compose_in_place(&mut nfkd);
+    nfkd
+}
+
+// Just the composition half of NFC, for text that's already in NFD (or NFKD, which is also NFD).
+// Anything else has to be decomposed first, or the result won't be normalised.
+pub fn compose(nfd: &[u32]) -> Vec<u32> {
+    debug_assert!(is_nfd(nfd), "compose expects its input to be in NFD");
+    let mut composed = nfd.to_vec();
+    compose_in_place(&mut composed);
+    composed
+}
+
+fn compose_in_place(nfd: &mut Vec<u32>) {
+    compose_from(nfd, 0)
+}
+
+// Composes the part of nfd from start on, leaving what's before it alone.
+fn compose_from(nfd: &mut Vec<u32>, start: usize) {
+    let mut pos = start;
     let mut try_compose = true;
     loop {
         if try_compose {
             try_compose = false;
             let char_seq_end = nfd[pos..]
-                .into_iter()
+                .iter()
                 .skip(1) // skip the current starter
                 .position(|cp| is_starter(*cp)) // find the next starter, idx is from pos.skip(1) not pos
                 .map(|offset| min(offset + 2, nfd.len() - pos)) // add one for beginning and ending starter
@@ -103,7 +466,7 @@ fn to_nfc(code_points: &Vec<u32>) -> Vec<u32> {
             let mut last_ccc = 0;
             for i in 1..char_seq_end {
                 let ccc = combining_class(nfd[pos + i]);
-                if let Some(composite) = primary_composite(nfd[pos], nfd[pos + i]) {
+                if let Some(composite) = canonical_composition(nfd[pos], nfd[pos + i]) {
                     // A starter-combining mark pair may be blocked by an intervening combining mark
                     // if they have equal combining classes. Given A B C, if A + C form a pair, but
                     // both B and C have the combining class 200, C is blocked by B.
@@ -141,7 +504,540 @@ fn to_nfc(code_points: &Vec<u32>) -> Vec<u32> {
             }
         }
     }
-    nfd
+}
+
+// Two strings are canonically equivalent if they have the same NFD. If they're both already in
+// NFD (or both in NFC), they're equivalent only if they're identical, so the quick check can often
+// skip normalising altogether. Otherwise they're decomposed lazily, so it stops at the first
+// difference.
+pub fn canonically_equivalent(x: &[u32], y: &[u32]) -> bool {
+    if x == y {
+        return true;
+    }
+    let both_are = |normalisation| {
+        quick_check(x, normalisation) == IsNormalised::Yes
+            && quick_check(y, normalisation) == IsNormalised::Yes
+    };
+    if both_are(Normalisation::NFD) || both_are(Normalisation::NFC) {
+        return false;
+    }
+    x.iter().copied().nfd().eq(y.iter().copied().nfd())
+}
+
+// Joins two NFC strings. The only part that might change is between the last safe starter in the
+// first string and the first safe starter in the second, so only that part is normalised again.
+pub fn nfc_concat(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let a_start = a
+        .iter()
+        .rposition(|cp| is_composition_boundary(*cp))
+        .unwrap_or(0);
+    let b_end = b
+        .iter()
+        .position(|cp| is_composition_boundary(*cp))
+        .unwrap_or(b.len());
+    let mut join = a[a_start..].to_vec();
+    join.extend_from_slice(&b[..b_end]);
+
+    let mut result = Vec::with_capacity(a.len() + b.len());
+    result.extend_from_slice(&a[..a_start]);
+    result.extend(to_nfc(&join));
+    result.extend_from_slice(&b[b_end..]);
+    result
+}
+
+// https://www.unicode.org/reports/tr15/#Stream_Safe_Text_Format
+// Nothing in Unicode needs more than 30 non-starters in a row, so if there are more than that, a
+// CGJ (which is a starter that doesn't do anything) gets put in to break up the run. That puts an
+// upper limit on how much anything processing the text has to buffer. The count goes by the
+// compatibility decomposition, because a single code point can decompose to several non-starters.
+const MAX_NON_STARTERS: usize = 30;
+const COMBINING_GRAPHEME_JOINER: u32 = 0x034F;
+
+// How many non-starters the code point decomposes to at the beginning and the end, and whether
+// it's all non-starters.
+fn non_starter_counts(cp: u32) -> (usize, usize, bool) {
+    let decomposed = decompose_compatibility(cp);
+    let leading = decomposed.iter().take_while(|c| !is_starter(**c)).count();
+    let trailing = decomposed
+        .iter()
+        .rev()
+        .take_while(|c| !is_starter(**c))
+        .count();
+    (leading, trailing, leading == decomposed.len())
+}
+
+pub fn to_stream_safe(code_points: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(code_points.len());
+    let mut non_starters = 0;
+    for cp in code_points {
+        let (leading, trailing, all_non_starters) = non_starter_counts(*cp);
+        if non_starters + leading > MAX_NON_STARTERS {
+            result.push(COMBINING_GRAPHEME_JOINER);
+            non_starters = 0;
+        }
+        non_starters = if all_non_starters {
+            non_starters + leading
+        } else {
+            trailing
+        };
+        result.push(*cp);
+    }
+    result
+}
+
+pub fn is_stream_safe(code_points: &[u32]) -> bool {
+    let mut non_starters = 0;
+    for cp in code_points {
+        let (leading, trailing, all_non_starters) = non_starter_counts(*cp);
+        if non_starters + leading > MAX_NON_STARTERS {
+            return false;
+        }
+        non_starters = if all_non_starters {
+            non_starters + leading
+        } else {
+            trailing
+        };
+    }
+    true
+}
+
+// Lazy versions of to_nfd and to_nfc, so normalisation can be chained onto other iterators
+// without collecting into a Vec first. They only buffer as much as they need to: canonical
+// ordering only happens between starters, so NFD can give out everything before the next starter.
+// Composition can combine a starter with the starter before it, but not if the second one is
+// NFC_QC=Yes, so NFC can give out everything before one of those.
+pub trait NormaliseIter: Iterator<Item = u32> + Sized {
+    fn decompositions(self) -> Decompositions<Self> {
+        Decompositions {
+            code_points: self,
+            segment: Vec::new(),
+            ready: VecDeque::new(),
+        }
+    }
+
+    fn nfd(self) -> Nfd<Self> {
+        Nfd(self.decompositions())
+    }
+
+    fn nfc(self) -> Nfc<Self> {
+        Nfc {
+            nfd: self.nfd(),
+            segment: Vec::new(),
+            ready: VecDeque::new(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = u32>> NormaliseIter for I {}
+
+type CharCodePoints<I> = std::iter::Map<I, fn(char) -> u32>;
+
+// The same, for chars, so they can go straight on the end of str::chars().
+pub trait NormaliseChars: Iterator<Item = char> + Sized {
+    fn nfd(self) -> NormalisedChars<Nfd<CharCodePoints<Self>>> {
+        let to_u32: fn(char) -> u32 = |c| c as u32;
+        NormalisedChars(self.map(to_u32).nfd())
+    }
+
+    fn nfc(self) -> NormalisedChars<Nfc<CharCodePoints<Self>>> {
+        let to_u32: fn(char) -> u32 = |c| c as u32;
+        NormalisedChars(self.map(to_u32).nfc())
+    }
+}
+
+impl<I: Iterator<Item = char>> NormaliseChars for I {}
+
+// Decomposes and reorders, giving out each code point with its combining class, so that anything
+// consuming it (like composition or collation) doesn't have to look the class up again. Nfd is
+// this without the classes.
+pub struct Decompositions<I> {
+    code_points: I,
+    segment: Vec<(u8, u32)>,
+    ready: VecDeque<(u8, u32)>,
+}
+
+impl<I: Iterator<Item = u32>> Iterator for Decompositions<I> {
+    type Item = (u8, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(pair) = self.ready.pop_front() {
+                return Some(pair);
+            }
+            match self.code_points.next() {
+                Some(cp) => {
+                    for d in decompose(cp) {
+                        let ccc = combining_class(d);
+                        if ccc == 0 && !self.segment.is_empty() {
+                            self.flush();
+                        }
+                        self.segment.push((ccc, d));
+                    }
+                }
+                None if self.segment.is_empty() => return None,
+                None => self.flush(),
+            }
+        }
+    }
+}
+
+impl<I> Decompositions<I> {
+    // Only the starter at the start of the segment has a class of 0, so sorting the whole thing
+    // leaves it where it is.
+    fn flush(&mut self) {
+        self.segment.sort_by_key(|(ccc, _)| *ccc);
+        self.ready.extend(self.segment.drain(..));
+    }
+}
+
+pub struct Nfd<I>(Decompositions<I>);
+
+impl<I: Iterator<Item = u32>> Iterator for Nfd<I> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(_, cp)| cp)
+    }
+}
+
+pub struct Nfc<I> {
+    nfd: Nfd<I>,
+    segment: Vec<u32>,
+    ready: VecDeque<u32>,
+}
+
+impl<I: Iterator<Item = u32>> Iterator for Nfc<I> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(cp) = self.ready.pop_front() {
+                return Some(cp);
+            }
+            match self.nfd.next() {
+                Some(cp) => {
+                    if is_composition_boundary(cp) && !self.segment.is_empty() {
+                        compose_in_place(&mut self.segment);
+                        self.ready.extend(self.segment.drain(..));
+                    }
+                    self.segment.push(cp);
+                }
+                None if self.segment.is_empty() => return None,
+                None => {
+                    compose_in_place(&mut self.segment);
+                    self.ready.extend(self.segment.drain(..));
+                }
+            }
+        }
+    }
+}
+
+pub struct NormalisedChars<I>(I);
+
+impl<I: Iterator<Item = u32>> Iterator for NormalisedChars<I> {
+    type Item = char;
+
+    // Normalising valid scalar values only ever produces valid scalar values.
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|cp| char::from_u32(cp).unwrap())
+    }
+}
+
+// Builds up an NFC string from pieces. Whatever is after the last composition boundary might
+// still combine with the next piece, so that's taken off and normalised together with it, and
+// everything before it stays as it is.
+#[derive(Debug, Default)]
+pub struct NfcBuilder {
+    code_points: Vec<u32>,
+}
+
+impl NfcBuilder {
+    pub fn new() -> Self {
+        NfcBuilder::default()
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.append(&[c as u32]);
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        let cps: Vec<u32> = s.chars().map(|c| c as u32).collect();
+        self.append(&cps);
+    }
+
+    fn append(&mut self, code_points: &[u32]) {
+        let start = self
+            .code_points
+            .iter()
+            .rposition(|cp| is_composition_boundary(*cp))
+            .unwrap_or(0);
+        let mut seam = self.code_points.split_off(start);
+        seam.extend_from_slice(code_points);
+        self.code_points.extend(to_nfc(&seam));
+    }
+
+    pub fn build(self) -> String {
+        self.code_points
+            .into_iter()
+            .map(|cp| char::from_u32(cp).unwrap())
+            .collect()
+    }
+}
+
+// For when the form is picked at runtime, from a config file or the like, rather than by choosing
+// which function to call.
+#[derive(Clone, Copy, Debug)]
+pub struct Normaliser {
+    normalisation: Normalisation,
+}
+
+impl Normaliser {
+    pub fn new(normalisation: Normalisation) -> Self {
+        Normaliser { normalisation }
+    }
+
+    pub fn normalise(&self, s: &str) -> String {
+        let cps: Vec<u32> = CodePointIter::new(s.as_bytes()).collect();
+        let normalised = match self.normalisation {
+            Normalisation::NFC => to_nfc(&cps),
+            Normalisation::NFD => to_nfd(&cps),
+            Normalisation::NFKC => to_nfkc(&cps),
+            Normalisation::NFKD => to_nfkd(&cps),
+        };
+        String::from_utf8(to_utf8(normalised, s.len())).unwrap()
+    }
+
+    pub fn is_normalised(&self, s: &str) -> bool {
+        let cps: Vec<u32> = CodePointIter::new(s.as_bytes()).collect();
+        match self.normalisation {
+            Normalisation::NFC => is_nfc(&cps),
+            Normalisation::NFD => is_nfd(&cps),
+            Normalisation::NFKC => is_nfkc(&cps),
+            Normalisation::NFKD => is_nfkd(&cps),
+        }
+    }
+
+    pub fn quick_check(&self, s: &str) -> IsNormalised {
+        let cps: Vec<u32> = CodePointIter::new(s.as_bytes()).collect();
+        quick_check(&cps, self.normalisation)
+    }
+}
+
+// Strings that are known to be in a particular form. They can only be made by normalising, or by
+// checking that the string is already normalised, so anything holding one doesn't need to check
+// again. Two strings are canonically equivalent exactly when their NFC (or NFD) forms are
+// identical, so the derived equality is canonical equivalence.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NfcString(String);
+
+impl NfcString {
+    pub fn new(s: &str) -> Self {
+        NfcString(nfc(s))
+    }
+
+    // Gives the string back if it isn't already NFC.
+    pub fn from_nfc(s: String) -> Result<Self, String> {
+        let cps: Vec<u32> = s.chars().map(|c| c as u32).collect();
+        if is_nfc(&cps) {
+            Ok(NfcString(s))
+        } else {
+            Err(s)
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Deref for NfcString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for NfcString {
+    fn eq(&self, other: &str) -> bool {
+        *self == NfcString::new(other)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NfdString(String);
+
+impl NfdString {
+    pub fn new(s: &str) -> Self {
+        NfdString(nfd(s))
+    }
+
+    pub fn from_nfd(s: String) -> Result<Self, String> {
+        let cps: Vec<u32> = s.chars().map(|c| c as u32).collect();
+        if is_nfd(&cps) {
+            Ok(NfdString(s))
+        } else {
+            Err(s)
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Deref for NfdString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for NfdString {
+    fn eq(&self, other: &str) -> bool {
+        *self == NfdString::new(other)
+    }
+}
+
+// Reader and writer adapters that normalise a byte stream to NFC as it goes through. The bytes
+// are checked to be valid UTF-8, and only the code points after the last composition boundary are
+// held back, since anything after that might still combine with them. An incomplete character at
+// the end of a chunk is also held back until the rest of it turns up.
+struct NfcStream {
+    pending: Vec<u8>,
+    segment: Vec<u32>,
+    offset: usize,
+}
+
+impl NfcStream {
+    fn new() -> Self {
+        NfcStream {
+            pending: Vec::new(),
+            segment: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        self.pending.extend_from_slice(bytes);
+        let complete = complete_len(&self.pending);
+        let code_points = decode_utf8(&self.pending[..complete])
+            .map_err(|(err, pos)| invalid_utf8(err, self.offset + pos))?;
+        self.offset += complete;
+        self.segment.extend(code_points);
+        self.pending.drain(..complete);
+
+        if let Some(boundary) = self
+            .segment
+            .iter()
+            .rposition(|cp| is_composition_boundary(*cp))
+        {
+            for cp in to_nfc(&self.segment[..boundary]) {
+                encode_utf8_into(cp, out);
+            }
+            self.segment.drain(..boundary);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            return Err(invalid_utf8(DecodeErr::IncompleteCharacter, self.offset));
+        }
+        for cp in to_nfc(&self.segment) {
+            encode_utf8_into(cp, out);
+        }
+        self.segment.clear();
+        Ok(())
+    }
+}
+
+pub struct NfcReader<R> {
+    inner: R,
+    stream: NfcStream,
+    out: Vec<u8>,
+    out_pos: usize,
+    done: bool,
+}
+
+impl<R: Read> NfcReader<R> {
+    pub fn new(inner: R) -> Self {
+        NfcReader {
+            inner,
+            stream: NfcStream::new(),
+            out: Vec::new(),
+            out_pos: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Read for NfcReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut chunk = [0; 4096];
+        while self.out_pos == self.out.len() && !self.done {
+            self.out.clear();
+            self.out_pos = 0;
+            let n = self.inner.read(&mut chunk)?;
+            if n == 0 {
+                self.stream.finish(&mut self.out)?;
+                self.done = true;
+            } else {
+                self.stream.push(&chunk[..n], &mut self.out)?;
+            }
+        }
+        let n = min(buf.len(), self.out.len() - self.out_pos);
+        buf[..n].copy_from_slice(&self.out[self.out_pos..self.out_pos + n]);
+        self.out_pos += n;
+        Ok(n)
+    }
+}
+
+// The last segment can't be written until it's known that nothing else is coming, so finish has
+// to be called at the end. Flushing only flushes what's safe to write.
+pub struct NfcWriter<W: Write> {
+    inner: W,
+    stream: NfcStream,
+    out: Vec<u8>,
+}
+
+impl<W: Write> NfcWriter<W> {
+    pub fn new(inner: W) -> Self {
+        NfcWriter {
+            inner,
+            stream: NfcStream::new(),
+            out: Vec::new(),
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.stream.finish(&mut self.out)?;
+        self.inner.write_all(&self.out)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for NfcWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.push(buf, &mut self.out)?;
+        self.inner.write_all(&self.out)?;
+        self.out.clear();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
 }
 
 #[cfg(test)]
@@ -179,6 +1075,26 @@ mod tests {
         assert_eq!(c[4], to_nfd(&c[4]));
     }
 
+    /*
+    #    NFKC
+    #      c4 == toNFKC(c1) == toNFKC(c2) == toNFKC(c3) == toNFKC(c4) == toNFKC(c5)
+    */
+    fn nfkc_conformance_test(c: Vec<Vec<u32>>) {
+        for source in c.iter() {
+            assert_eq!(c[3], to_nfkc(source));
+        }
+    }
+
+    /*
+    #    NFKD
+    #      c5 == toNFKD(c1) == toNFKD(c2) == toNFKD(c3) == toNFKD(c4) == toNFKD(c5)
+    */
+    fn nfkd_conformance_test(c: Vec<Vec<u32>>) {
+        for source in c.iter() {
+            assert_eq!(c[4], to_nfkd(source));
+        }
+    }
+
     fn parse_line(line: &str) -> Vec<Vec<u32>> {
         line.split(";")
             .take(5)
@@ -204,21 +1120,385 @@ mod tests {
     fn test_quick_check() {
         // "å"
         assert_eq!(
-            quick_check(&vec![0x00E5], Normalisation::NFC),
+            quick_check(&[0x00E5], Normalisation::NFC),
             IsNormalised::Yes
         );
         // "å" decomposed, quick check says maybe, because there are combining marks
         // it's actually not normalised, but those code points could make up a normalised string
         assert_eq!(
-            quick_check(&vec![0x61, 0x030A], Normalisation::NFC),
+            quick_check(&[0x61, 0x030A], Normalisation::NFC),
             IsNormalised::Maybe
         );
 
+        // "ﬁ" is fine in NFC, but not NFKC
+        assert_eq!(
+            quick_check(&[0xFB01], Normalisation::NFC),
+            IsNormalised::Yes
+        );
+        assert_eq!(
+            quick_check(&[0xFB01], Normalisation::NFKC),
+            IsNormalised::No
+        );
+        assert_eq!(
+            quick_check(&[0xFB01], Normalisation::NFKD),
+            IsNormalised::No
+        );
+
         for case in load_test_cases() {
             // This is the NFC normalised case, so quick check can only identify that it's not not normalised.
             assert_ne!(quick_check(&case[1], Normalisation::NFC), IsNormalised::No);
             // NFD normalised case. There's no maybe decomposed, so we know this is yes.
             assert_eq!(quick_check(&case[2], Normalisation::NFD), IsNormalised::Yes);
+            assert_ne!(quick_check(&case[3], Normalisation::NFKC), IsNormalised::No);
+            assert_eq!(
+                quick_check(&case[4], Normalisation::NFKD),
+                IsNormalised::Yes
+            );
+        }
+    }
+
+    #[test]
+    fn test_quick_check_position() {
+        assert_eq!(
+            quick_check_position(&[0x61, 0x62], Normalisation::NFC),
+            (IsNormalised::Yes, None)
+        );
+        assert_eq!(
+            quick_check_position(&[0x61, 0x62, 0x030A, 0x0340], Normalisation::NFC),
+            (IsNormalised::No, Some(2))
+        );
+        assert_eq!(
+            quick_check_position(&[0x61, 0x00E5], Normalisation::NFD),
+            (IsNormalised::No, Some(1))
+        );
+        // out of order combining marks
+        assert_eq!(
+            quick_check_position(&[0x61, 0x0301, 0x0316], Normalisation::NFD),
+            (IsNormalised::No, Some(2))
+        );
+    }
+
+    #[test]
+    fn test_to_nfd_into() {
+        let mut buf = Vec::new();
+        for case in load_test_cases() {
+            to_nfd_into(&case[0], &mut buf);
+            assert_eq!(buf, case[2]);
+            to_nfc_into(&case[0], &mut buf);
+            assert_eq!(buf, case[1]);
+        }
+    }
+
+    #[test]
+    fn test_decompose_compat() {
+        assert_eq!(
+            decompose_compat(0x00A0),
+            Some((DecompositionType::Nb, vec![0x20]))
+        );
+        assert_eq!(
+            decompose_compat(0xFF21),
+            Some((DecompositionType::Wide, vec![0x41]))
+        );
+        assert_eq!(
+            decompose_compat(0x00BD),
+            Some((DecompositionType::Fra, vec![0x31, 0x2044, 0x32]))
+        );
+        assert_eq!(
+            decompose_compat(0x2460),
+            Some((DecompositionType::Enc, vec![0x31]))
+        );
+        // canonical mappings aren't included
+        assert_eq!(decompose_compat(0x00C5), None);
+        assert_eq!(decompose_compat(0x61), None);
+    }
+
+    #[test]
+    fn test_try_to_nfc() {
+        assert_eq!(try_to_nfc(&[0x61, 0x030A]), Ok(vec![0x00E5]));
+        assert_eq!(
+            try_to_nfc(&[0x61, 0x0378]),
+            Err((NormalisationErr::Unassigned, 1))
+        );
+        assert_eq!(
+            try_to_nfc(&[0xD800, 0x61]),
+            Err((NormalisationErr::InvalidCodePoint, 0))
+        );
+        assert_eq!(
+            try_to_nfc(&[0x61, 0x110000]),
+            Err((NormalisationErr::InvalidCodePoint, 1))
+        );
+        // private use is assigned
+        assert_eq!(try_to_nfc(&[0xE000]), Ok(vec![0xE000]));
+    }
+
+    #[test]
+    fn test_canonical_reorder() {
+        let mut cps = [0x61, 0x0301, 0x0316, 0x62, 0x0308, 0x0323, 0x0302];
+        canonical_reorder(&mut cps);
+        assert_eq!(cps, [0x61, 0x0316, 0x0301, 0x62, 0x0323, 0x0308, 0x0302]);
+        // a leading run of non-starters
+        let mut cps = [0x0301, 0x0316];
+        canonical_reorder(&mut cps);
+        assert_eq!(cps, [0x0316, 0x0301]);
+    }
+
+    #[test]
+    fn test_compose() {
+        assert_eq!(compose(&[0x61, 0x030A, 0x62]), [0x00E5, 0x62]);
+        assert_eq!(compose(&[0x1100, 0x1161, 0x11A8]), [0xAC01]);
+        for case in load_test_cases() {
+            assert_eq!(compose(&case[2]), case[1]);
+            assert_eq!(compose(&case[4]), case[3]);
+        }
+    }
+
+    #[test]
+    fn test_fast_path() {
+        assert_eq!(
+            to_nfd(&[0x61, 0x62, 0x00C5, 0x63]),
+            [0x61, 0x62, 0x41, 0x030A, 0x63]
+        );
+        assert_eq!(
+            to_nfc(&[0x61, 0x62, 0x41, 0x030A, 0x63]),
+            [0x61, 0x62, 0x00C5, 0x63]
+        );
+        assert_eq!(to_nfc(&[0x61, 0x00C5]), [0x61, 0x00C5]);
+        assert_eq!(to_nfc(&[0x41, 0x0323, 0x030A]), [0x1EA0, 0x030A]);
+        assert!(to_nfc(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_nfc_reader_writer() {
+        let input = "Ame\u{0301}lie a\u{030A} \u{1100}\u{1161}\u{11A8}".repeat(500);
+        let expected = nfc(&input);
+
+        let mut output = String::new();
+        NfcReader::new(input.as_bytes())
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, expected);
+
+        // split up so that characters and segments are cut in half
+        let mut writer = NfcWriter::new(Vec::new());
+        for chunk in input.as_bytes().chunks(7) {
+            writer.write_all(chunk).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), expected.as_bytes());
+
+        let mut writer = NfcWriter::new(Vec::new());
+        assert!(writer.write_all(b"ab\xFFc").is_err());
+        let mut writer = NfcWriter::new(Vec::new());
+        writer.write_all(b"ab\xC3").unwrap();
+        assert!(writer.finish().is_err());
+    }
+
+    #[test]
+    fn test_nfc_nfd_str() {
+        assert_eq!(nfc("Ame\u{0301}lie"), "Am\u{00E9}lie");
+        assert_eq!(nfd("Am\u{00E9}lie"), "Ame\u{0301}lie");
+        assert_eq!(nfc("\u{1100}\u{1161}\u{11A8}"), "\u{AC01}");
+        assert_eq!(nfd(""), "");
+    }
+
+    #[test]
+    fn test_nfc_builder() {
+        let mut builder = NfcBuilder::new();
+        builder.push_str("Ame");
+        builder.push('\u{0301}');
+        builder.push_str("lie \u{1100}");
+        builder.push('\u{1161}');
+        builder.push_str("\u{11A8}a\u{0316}");
+        builder.push('\u{030A}');
+        assert_eq!(builder.build(), "Am\u{00E9}lie \u{AC01}\u{00E5}\u{0316}");
+        assert_eq!(NfcBuilder::new().build(), "");
+    }
+
+    #[test]
+    fn test_normaliser() {
+        let nfkc = Normaliser::new(Normalisation::NFKC);
+        assert_eq!(nfkc.normalise("\u{FB01}a\u{030A}"), "fi\u{00E5}");
+        assert!(nfkc.is_normalised("fi\u{00E5}"));
+        assert!(!nfkc.is_normalised("\u{FB01}"));
+        assert_eq!(nfkc.quick_check("a\u{030A}"), IsNormalised::Maybe);
+
+        let nfd = Normaliser::new(Normalisation::NFD);
+        assert_eq!(nfd.normalise("\u{FB01}\u{00E5}"), "\u{FB01}a\u{030A}");
+        assert_eq!(nfd.quick_check("\u{00E5}"), IsNormalised::No);
+    }
+
+    #[test]
+    fn test_normalised_strings() {
+        let composed = NfcString::new("a\u{030A}");
+        assert_eq!(composed.as_str(), "\u{00E5}");
+        assert_eq!(composed.len(), 2);
+        assert!(composed == *"a\u{030A}");
+        assert_eq!(NfcString::from_nfc("\u{00E5}".to_string()), Ok(composed));
+        assert!(NfcString::from_nfc("a\u{030A}".to_string()).is_err());
+
+        let decomposed = NfdString::new("\u{00E5}");
+        assert_eq!(&*decomposed, "a\u{030A}");
+        assert!(decomposed == *"\u{00E5}");
+        assert!(NfdString::from_nfd("\u{00E5}".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_composition_boundary() {
+        assert!(is_composition_boundary(0x61));
+        assert!(!is_composition_boundary(0x030A));
+        // a starter, but it can compose with a preceding L
+        assert!(!is_composition_boundary(0x1161));
+        let cps = [0x61, 0x030A, 0x1100, 0x1161, 0x62];
+        assert_eq!(next_boundary(&cps, 0), 2);
+        assert_eq!(next_boundary(&cps, 2), 4);
+        assert_eq!(next_boundary(&cps, 4), 5);
+    }
+
+    #[test]
+    fn test_canonically_equivalent() {
+        assert!(canonically_equivalent(&[0x00E5], &[0x61, 0x030A]));
+        assert!(canonically_equivalent(&[0x212B], &[0x00C5]));
+        assert!(canonically_equivalent(
+            &[0x61, 0x0301, 0x0316],
+            &[0x61, 0x0316, 0x0301]
+        ));
+        assert!(!canonically_equivalent(&[0x61], &[0x41]));
+        // compatibility equivalent isn't enough
+        assert!(!canonically_equivalent(&[0xFB01], &[0x66, 0x69]));
+
+        for case in load_test_cases() {
+            assert!(canonically_equivalent(&case[0], &case[1]));
+            assert!(canonically_equivalent(&case[1], &case[2]));
+        }
+    }
+
+    #[test]
+    fn test_nfc_concat() {
+        assert_eq!(
+            nfc_concat(&[0x78, 0x61], &[0x030A, 0x62]),
+            [0x78, 0x00E5, 0x62]
+        );
+        assert_eq!(nfc_concat(&[], &[0x0301, 0x62]), [0x0301, 0x62]);
+        // Hangul LV + T
+        assert_eq!(nfc_concat(&[0xAC00], &[0x11A8]), [0xAC01]);
+        // the marks have to be reordered across the join
+        assert_eq!(
+            nfc_concat(&[0x78, 0x0301], &[0x0316]),
+            [0x78, 0x0316, 0x0301]
+        );
+
+        let cases = load_test_cases();
+        for pair in cases.windows(2) {
+            let mut joined = pair[0][1].clone();
+            joined.extend(&pair[1][1]);
+            assert_eq!(nfc_concat(&pair[0][1], &pair[1][1]), to_nfc(&joined));
+        }
+    }
+
+    #[test]
+    fn test_detect_forms() {
+        let all = detect_forms(&[0x61, 0x62]);
+        assert!(all.contains(Normalisation::NFC));
+        assert!(all.contains(Normalisation::NFD));
+        assert!(all.contains(Normalisation::NFKC));
+        assert!(all.contains(Normalisation::NFKD));
+
+        let composed = detect_forms(&[0x00E5, 0xFB01]);
+        assert!(composed.contains(Normalisation::NFC));
+        assert!(!composed.contains(Normalisation::NFD));
+        assert!(!composed.contains(Normalisation::NFKC));
+
+        let decomposed = detect_forms(&[0x61, 0x030A]);
+        assert!(!decomposed.contains(Normalisation::NFC));
+        assert!(decomposed.contains(Normalisation::NFKD));
+
+        assert!(detect_forms(&[0xFB01, 0x00E5, 0x61, 0x030A]).is_empty());
+
+        for case in load_test_cases() {
+            let forms = detect_forms(&case[3]);
+            assert!(forms.contains(Normalisation::NFKC));
+            assert!(forms.contains(Normalisation::NFC));
+        }
+    }
+
+    #[test]
+    fn test_stream_safe() {
+        let mut cps = vec![0x61];
+        cps.extend([0x0301; 30]);
+        assert!(is_stream_safe(&cps));
+        assert_eq!(to_stream_safe(&cps), cps);
+
+        cps.push(0x0301);
+        assert!(!is_stream_safe(&cps));
+        let safe = to_stream_safe(&cps);
+        assert!(is_stream_safe(&safe));
+        assert_eq!(safe[31], COMBINING_GRAPHEME_JOINER);
+        assert_eq!(safe.len(), 33);
+
+        // U+0344 decomposes to two non-starters, so it counts twice
+        let mut cps = vec![0x61];
+        cps.extend([0x0301; 29]);
+        cps.push(0x0344);
+        assert!(!is_stream_safe(&cps));
+    }
+
+    #[test]
+    fn test_quick_check_utf8() {
+        let ascii = "plenty of plain ascii text before anything else".as_bytes();
+        assert_eq!(
+            quick_check_utf8(ascii, Normalisation::NFC),
+            Ok(IsNormalised::Yes)
+        );
+        let text = "plenty of plain ascii text before a\u{030A}".as_bytes();
+        assert_eq!(
+            quick_check_utf8(text, Normalisation::NFC),
+            Ok(IsNormalised::Maybe)
+        );
+        assert_eq!(
+            quick_check_utf8(text, Normalisation::NFD),
+            Ok(IsNormalised::Yes)
+        );
+        let text = "plenty of plain ascii text before \u{00E5}".as_bytes();
+        assert_eq!(
+            quick_check_utf8(text, Normalisation::NFD),
+            Ok(IsNormalised::No)
+        );
+        assert_eq!(
+            quick_check_utf8(b"", Normalisation::NFKD),
+            Ok(IsNormalised::Yes)
+        );
+        assert_eq!(
+            quick_check_utf8(b"ascii then \xC3(", Normalisation::NFC),
+            Err((DecodeErr::IncompleteCharacter, 11))
+        );
+        assert_eq!(
+            quick_check_utf8(b"\xE0\x80\x80", Normalisation::NFD),
+            Err((DecodeErr::OverlongEncoding, 0))
+        );
+        // It stops at the å, so it never gets to the bad byte after it, but one before it is
+        // still an error.
+        assert_eq!(
+            quick_check_utf8(b"\xC3\xA5\xFF", Normalisation::NFD),
+            Ok(IsNormalised::No)
+        );
+        assert_eq!(
+            quick_check_utf8(b"\xFF\xC3\xA5", Normalisation::NFD),
+            Err((DecodeErr::InvalidCodeUnit, 0))
+        );
+    }
+
+    #[test]
+    fn test_is_nfc() {
+        assert!(is_nfc(&[0x00E5]));
+        assert!(!is_nfc(&[0x61, 0x030A]));
+        // U+0301 is a Maybe, but there's nothing for it to compose with
+        assert!(is_nfc(&[0x78, 0x0301, 0x62]));
+        assert!(is_nfd(&[0x61, 0x030A]));
+        assert!(!is_nfd(&[0x00E5]));
+
+        for case in load_test_cases() {
+            assert!(is_nfc(&case[1]));
+            assert!(is_nfd(&case[2]));
+            assert_eq!(is_nfc(&case[0]), case[0] == case[1]);
         }
     }
 
@@ -235,4 +1515,61 @@ mod tests {
             nfd_conformance_test(case)
         }
     }
+
+    #[test]
+    fn test_to_nfc_str() {
+        let composed = "\u{00E5}b".as_bytes();
+        let decomposed = "a\u{030A}b".as_bytes();
+        assert!(matches!(to_nfc_str(composed), Ok(Cow::Borrowed(_))));
+        assert!(matches!(to_nfd_str(decomposed), Ok(Cow::Borrowed(_))));
+        assert_eq!(to_nfc_str(decomposed).unwrap(), composed);
+        assert_eq!(to_nfd_str(composed).unwrap(), decomposed);
+        assert_eq!(
+            to_nfc_str(b"a\xCC\x8A\xFF"),
+            Err((DecodeErr::InvalidCodeUnit, 3))
+        );
+        assert_eq!(
+            to_nfd_str(b"\xC3\xA5\xED\xA0\x80"),
+            Err((DecodeErr::InvalidCodePoint, 2))
+        );
+    }
+
+    #[test]
+    fn test_iter_adapters() {
+        for c in load_test_cases() {
+            assert_eq!(c[1], c[0].iter().copied().nfc().collect::<Vec<u32>>());
+            assert_eq!(c[2], c[0].iter().copied().nfd().collect::<Vec<u32>>());
+            assert_eq!(c[3], c[4].iter().copied().nfc().collect::<Vec<u32>>());
+        }
+        // å, decomposed and composed.
+        assert_eq!("a\u{030A}b".chars().nfc().collect::<String>(), "\u{00E5}b");
+        assert_eq!("\u{00E5}b".chars().nfd().collect::<String>(), "a\u{030A}b");
+    }
+
+    #[test]
+    fn test_decompositions() {
+        let pairs: Vec<(u8, u32)> = [0x1E0B, 0x0323, 0x62]
+            .into_iter()
+            .decompositions()
+            .collect();
+        assert_eq!(pairs, [(0, 0x64), (220, 0x0323), (230, 0x0307), (0, 0x62)]);
+        // doesn't need to see the end of the input to get started
+        let mut endless = [0x61, 0x030A].into_iter().cycle().decompositions();
+        assert_eq!(endless.next(), Some((0, 0x61)));
+        assert_eq!(endless.next(), Some((230, 0x030A)));
+    }
+
+    #[test]
+    fn test_to_nfkc() {
+        for case in load_test_cases() {
+            nfkc_conformance_test(case)
+        }
+    }
+
+    #[test]
+    fn test_to_nfkd() {
+        for case in load_test_cases() {
+            nfkd_conformance_test(case)
+        }
+    }
 }
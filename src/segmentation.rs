@@ -1,10 +1,24 @@
-// Implementation of default, non-locale specific grapheme cluster boundaries.
+// Implementation of default, non-locale specific grapheme cluster, word and sentence boundaries,
+// and line breaks.
 
-use crate::ucd::{extended_pictorial, grapheme_cluster_break, GraphemeClusterBreak};
+use crate::cp_iter::CodePointIter;
+use crate::helpers::{complete_len, invalid_utf8, DecodeErr};
+use crate::normalise::to_nfc;
+use crate::trie::{Trie, TrieMatch};
+use crate::ucd::{
+    east_asian_wide, emoji_presentation, extended_pictorial, grapheme_cluster_break,
+    indic_conjunct_break, is_assigned, line_break, sentence_break, word_break,
+    GraphemeClusterBreak, IndicConjunctBreak, LineBreak, SentenceBreak, WordBreak,
+};
+use crate::validate::{decode_utf8, validate};
+use std::cmp::Reverse;
+use std::io;
+use std::ops::Range;
 
 pub struct GraphemeIter<'a> {
     code_points: &'a Vec<u32>,
     pos: usize,
+    end: usize,
 }
 
 impl<'a> GraphemeIter<'a> {
@@ -12,85 +26,1748 @@ impl<'a> GraphemeIter<'a> {
         GraphemeIter {
             code_points,
             pos: 0,
+            end: code_points.len(),
         }
     }
+
+    // The rule that ended the last cluster next returned, like GB5 or GB999. It's only worked out
+    // when it's asked for, so iterating without it costs nothing.
+    pub fn rule(&self) -> &'static str {
+        grapheme_boundary_rule(self.code_points, self.pos).1
+    }
+}
+
+impl<'a> Iterator for GraphemeIter<'a> {
+    type Item = &'a [u32];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.end {
+            return None;
+        }
+        let start = self.pos;
+        self.pos = grapheme_end(self.code_points, start).min(self.end);
+        Some(&self.code_points[start..self.pos])
+    }
+}
+
+// Going backwards can't use the same state as going forwards, because the state depends on the
+// start of the cluster, which is what it's looking for. So it checks each position for a
+// boundary instead, looking back as far as the rule needs.
+impl<'a> DoubleEndedIterator for GraphemeIter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.end <= self.pos {
+            return None;
+        }
+        let end = self.end;
+        self.end = grapheme_start(self.code_points, end).max(self.pos);
+        Some(&self.code_points[self.end..end])
+    }
+}
+
+// The same clusters, but as slices of a string, which is what's needed to draw them or move a
+// cursor over them. The string gets decoded up front, keeping the byte offset of each code point,
+// so a cluster's code points can be turned back into the part of the string they came from.
+pub struct Graphemes<'a> {
+    s: &'a str,
+    code_points: Vec<u32>,
+    offsets: Vec<usize>,
+    pos: usize,
+    end: usize,
+}
+
+impl<'a> Graphemes<'a> {
+    pub fn new(s: &'a str) -> Self {
+        let (offsets, code_points): (Vec<usize>, Vec<u32>) =
+            s.char_indices().map(|(i, c)| (i, c as u32)).unzip();
+        let end = code_points.len();
+        Graphemes {
+            s,
+            code_points,
+            offsets,
+            pos: 0,
+            end,
+        }
+    }
+
+    fn byte_offset(&self, pos: usize) -> usize {
+        self.offsets.get(pos).copied().unwrap_or(self.s.len())
+    }
+}
+
+impl<'a> Iterator for Graphemes<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.end {
+            return None;
+        }
+        let start = self.byte_offset(self.pos);
+        self.pos = grapheme_end(&self.code_points, self.pos).min(self.end);
+        Some(&self.s[start..self.byte_offset(self.pos)])
+    }
+}
+
+impl<'a> DoubleEndedIterator for Graphemes<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.end <= self.pos {
+            return None;
+        }
+        let end = self.byte_offset(self.end);
+        self.end = grapheme_start(&self.code_points, self.end).max(self.pos);
+        Some(&self.s[self.byte_offset(self.end)..end])
+    }
+}
+
+// Each cluster with the byte offset it starts at.
+pub struct GraphemeIndices<'a> {
+    graphemes: Graphemes<'a>,
+}
+
+impl<'a> GraphemeIndices<'a> {
+    pub fn new(s: &'a str) -> Self {
+        GraphemeIndices {
+            graphemes: Graphemes::new(s),
+        }
+    }
+}
+
+impl<'a> Iterator for GraphemeIndices<'a> {
+    type Item = (usize, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.graphemes.byte_offset(self.graphemes.pos);
+        self.graphemes.next().map(|grapheme| (offset, grapheme))
+    }
+}
+
+impl<'a> DoubleEndedIterator for GraphemeIndices<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let grapheme = self.graphemes.next_back()?;
+        Some((self.graphemes.byte_offset(self.graphemes.end), grapheme))
+    }
+}
+
+// Each cluster with the offset it starts at in UTF-16 code units, which is how JavaScript, Windows
+// and the Language Server Protocol index text. Everything outside the BMP takes two units.
+pub struct GraphemeUtf16Indices<'a> {
+    graphemes: Graphemes<'a>,
+    front: usize,
+    back: usize,
+}
+
+impl<'a> GraphemeUtf16Indices<'a> {
+    pub fn new(s: &'a str) -> Self {
+        GraphemeUtf16Indices {
+            graphemes: Graphemes::new(s),
+            front: 0,
+            back: utf16_len(s),
+        }
+    }
+}
+
+fn utf16_len(s: &str) -> usize {
+    s.chars().map(char::len_utf16).sum()
+}
+
+impl<'a> Iterator for GraphemeUtf16Indices<'a> {
+    type Item = (usize, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let grapheme = self.graphemes.next()?;
+        let offset = self.front;
+        self.front += utf16_len(grapheme);
+        Some((offset, grapheme))
+    }
+}
+
+impl<'a> DoubleEndedIterator for GraphemeUtf16Indices<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let grapheme = self.graphemes.next_back()?;
+        self.back -= utf16_len(grapheme);
+        Some((self.back, grapheme))
+    }
+}
+
+// The number of user-perceived characters, e.g. for limiting the length of a name.
+pub fn grapheme_count(s: &str) -> usize {
+    Graphemes::new(s).count()
+}
+
+// The first n clusters, so that cutting a string short doesn't leave half an emoji or an accent
+// without its letter.
+pub fn truncate_graphemes(s: &str, n: usize) -> &str {
+    match GraphemeIndices::new(s).nth(n) {
+        Some((offset, _)) => &s[..offset],
+        None => s,
+    }
+}
+
+// The clusters in range, e.g. for showing characters 10 to 20 without cutting one in half. Goes
+// through the clusters once, stopping at the end of the range. Indices past the end are clamped to
+// it, and an empty or backwards range gives an empty string.
+pub fn grapheme_slice(s: &str, range: Range<usize>) -> &str {
+    let mut start = s.len();
+    let mut end = s.len();
+    for (i, (offset, _)) in GraphemeIndices::new(s).enumerate() {
+        if i == range.start {
+            start = offset;
+        }
+        if i == range.end {
+            end = offset;
+            break;
+        }
+    }
+    &s[start.min(end)..end]
+}
+
+// How many terminal columns a cluster takes up. The cluster is as wide as its widest code point,
+// so that marks and the vowels and finals of conjoining Hangul don't add anything. A variation
+// selector can switch a pictograph between emoji (2 columns) and text (1 column).
+pub fn cluster_width(cluster: &[u32]) -> usize {
+    if let [first, rest @ ..] = cluster {
+        if extended_pictorial(*first) {
+            if rest.contains(&0xFE0F) {
+                return 2;
+            } else if rest.contains(&0xFE0E) {
+                return 1;
+            }
+        }
+    }
+    cluster
+        .iter()
+        .map(|cp| code_point_width(*cp))
+        .max()
+        .unwrap_or(0)
+}
+
+pub fn str_width(s: &str) -> usize {
+    let code_points: Vec<u32> = s.chars().map(|c| c as u32).collect();
+    GraphemeIter::new(&code_points).map(cluster_width).sum()
+}
+
+fn code_point_width(code_point: u32) -> usize {
+    use GraphemeClusterBreak::*;
+    let gcb = grapheme_cluster_break(code_point);
+    if matches!(gcb, CN | CR | LF) {
+        0
+    } else if emoji_presentation(code_point) {
+        // Before the marks, because the skin tone modifiers are EX, but shown on their own
+        // they're as wide as any other emoji.
+        2
+    } else if matches!(gcb, EX | ZWJ | V | T) {
+        0
+    } else if east_asian_wide(code_point) {
+        2
+    } else {
+        1
+    }
+}
+
+// What the rules that only look at the pair of properties either side of a position say about it.
+// The rest, GB9c, GB11 and GB12/13, need to look further back, so the pairs they might apply to
+// are Contextual, and break unless one of them says not to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PairRule {
+    Break(&'static str),
+    NoBreak(&'static str),
+    Contextual,
+}
+
+const fn pair_rule(prev: GraphemeClusterBreak, next: GraphemeClusterBreak) -> PairRule {
+    use GraphemeClusterBreak::*;
+    match (prev, next) {
+        (CR, LF) => PairRule::NoBreak("GB3"),
+        (CN | CR | LF, _) => PairRule::Break("GB4"),
+        (_, CN | CR | LF) => PairRule::Break("GB5"),
+        (L, L | V | LV | LVT) => PairRule::NoBreak("GB6"),
+        (LV | V, V | T) => PairRule::NoBreak("GB7"),
+        (LVT | T, T) => PairRule::NoBreak("GB8"),
+        (_, EX | ZWJ) => PairRule::NoBreak("GB9"),
+        (_, SM) => PairRule::NoBreak("GB9a"),
+        (PP, _) => PairRule::NoBreak("GB9b"),
+        _ => PairRule::Contextual,
+    }
+}
+
+// pair_rule for every pair, indexed by the properties, worked out at compile time so that finding
+// a boundary is one lookup instead of going through the rules.
+static GRAPHEME_PAIR_RULES: [[PairRule; 18]; 18] = {
+    use GraphemeClusterBreak::*;
+    let all = [
+        CN, CR, EB, EBG, EM, EX, GAZ, L, LF, LV, LVT, PP, RI, SM, T, V, XX, ZWJ,
+    ];
+    let mut table = [[PairRule::Contextual; 18]; 18];
+    let mut i = 0;
+    while i < all.len() {
+        let mut j = 0;
+        while j < all.len() {
+            table[all[i] as usize][all[j] as usize] = pair_rule(all[i], all[j]);
+            j += 1;
+        }
+        i += 1;
+    }
+    table
+};
+
+// https://unicode.org/reports/tr29/#Grapheme_Cluster_Boundary_Rules
+// The end of the cluster that starts at start. At each char, we decide whether to break or keep
+// going.
+fn grapheme_end(code_points: &[u32], start: usize) -> usize {
+    let mut state = ClusterState::new();
+    let mut pos = start;
+    while pos < code_points.len() - 1 && state.joins(code_points[pos], code_points[pos + 1]) {
+        pos += 1;
+    }
+    pos + 1
+}
+
+// What grapheme_end needs to remember about the cluster so far, for the rules that look further
+// back than the last code point.
+struct ClusterState {
+    ri_count: usize,
+    // For GB11, whether the cluster so far ends in \p{Extended_Pictographic} Extend*, so that
+    // a ZWJ after it joins the next pictograph on.
+    ext_pict_run: bool,
+    // For GB9c, how far into Consonant [Extend Linker]* Linker [Extend Linker]* the cluster is.
+    conjunct: IndicConjunctBreak,
+}
+
+impl ClusterState {
+    fn new() -> Self {
+        ClusterState {
+            ri_count: 0,
+            ext_pict_run: false,
+            conjunct: IndicConjunctBreak::None,
+        }
+    }
+
+    // Whether next joins the cluster that last is currently the end of. It has to be called for
+    // each pair in turn from the start of the cluster.
+    fn joins(&mut self, last: u32, next: u32) -> bool {
+        use GraphemeClusterBreak::*;
+        let cp = grapheme_cluster_break(last);
+        let next_gcb = grapheme_cluster_break(next);
+        self.ri_count = if cp == RI { self.ri_count + 1 } else { 0 };
+        let after_ext_pict = cp == ZWJ && self.ext_pict_run;
+        self.ext_pict_run = extended_pictorial(last) || (cp == EX && self.ext_pict_run);
+        self.conjunct = match (self.conjunct, indic_conjunct_break(last)) {
+            (_, IndicConjunctBreak::Consonant) => IndicConjunctBreak::Consonant,
+            (IndicConjunctBreak::None, _) => IndicConjunctBreak::None,
+            (_, IndicConjunctBreak::Linker) => IndicConjunctBreak::Linker,
+            (state, IndicConjunctBreak::Extend) => state,
+            (_, IndicConjunctBreak::None) => IndicConjunctBreak::None,
+        };
+        match GRAPHEME_PAIR_RULES[cp as usize][next_gcb as usize] {
+            PairRule::NoBreak(_) => true,
+            PairRule::Break(_) => false,
+            PairRule::Contextual => {
+                let gb9c = self.conjunct == IndicConjunctBreak::Linker
+                    && indic_conjunct_break(next) == IndicConjunctBreak::Consonant;
+                let gb11 = cp == ZWJ && after_ext_pict && extended_pictorial(next);
+                let gb12_13 = cp == RI && next_gcb == RI && self.ri_count % 2 == 1;
+                gb9c || gb11 || gb12_13 // or GB999
+            }
+        }
+    }
+}
+
+// The clusters in UTF-8, decoding as it goes rather than up front, so it only ever holds onto one
+// code point past the end of the current cluster. The bytes are validated when it's made, since
+// CodePointIter trusts them, but that doesn't allocate.
+pub struct ByteGraphemes<'a> {
+    bytes: &'a [u8],
+    code_points: CodePointIter<&'a [u8]>,
+    // The first code point of the next cluster, and its offset.
+    next: Option<(u32, usize)>,
+}
+
+impl<'a> ByteGraphemes<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self, (DecodeErr, usize)> {
+        validate(bytes)?;
+        Ok(Self::new_unchecked(bytes))
+    }
+
+    fn new_unchecked(bytes: &'a [u8]) -> Self {
+        let mut code_points = CodePointIter::new(bytes);
+        let next = code_points.next().map(|cp| (cp, 0));
+        ByteGraphemes {
+            bytes,
+            code_points,
+            next,
+        }
+    }
+
+    fn next_range(&mut self) -> Option<Range<usize>> {
+        let (mut last, start) = self.next.take()?;
+        let mut state = ClusterState::new();
+        loop {
+            let offset = self.code_points.position();
+            match self.code_points.next() {
+                None => return Some(start..self.bytes.len()),
+                Some(cp) if state.joins(last, cp) => last = cp,
+                Some(cp) => {
+                    self.next = Some((cp, offset));
+                    return Some(start..offset);
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for ByteGraphemes<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_range().map(|range| &self.bytes[range])
+    }
+}
+
+// The same, but for a str, without the up front decoding that Graphemes does. It only goes
+// forwards, though.
+pub struct LazyGraphemes<'a> {
+    s: &'a str,
+    graphemes: ByteGraphemes<'a>,
+}
+
+impl<'a> LazyGraphemes<'a> {
+    pub fn new(s: &'a str) -> Self {
+        LazyGraphemes {
+            s,
+            graphemes: ByteGraphemes::new_unchecked(s.as_bytes()),
+        }
+    }
+}
+
+impl<'a> Iterator for LazyGraphemes<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.graphemes.next_range().map(|range| &self.s[range])
+    }
+}
+
+// The start of the cluster that ends at end.
+fn grapheme_start(code_points: &[u32], end: usize) -> usize {
+    let mut pos = end - 1;
+    while pos > 0 && !is_grapheme_boundary(code_points, pos) {
+        pos -= 1;
+    }
+    pos
+}
+
+// Random access to the boundaries, for moving a cursor, without iterating from the start. The
+// nearest boundary after pos, or the end if there isn't one.
+pub fn next_grapheme_boundary(code_points: &[u32], pos: usize) -> usize {
+    (pos + 1..code_points.len())
+        .find(|i| is_grapheme_boundary(code_points, *i))
+        .unwrap_or(code_points.len())
+}
+
+// The nearest boundary before pos, or 0.
+pub fn prev_grapheme_boundary(code_points: &[u32], pos: usize) -> usize {
+    match pos.min(code_points.len()) {
+        0 => 0,
+        pos => grapheme_start(code_points, pos),
+    }
+}
+
+// The same, but for byte offsets in a string. The rules never look past a line feed, because
+// there's always a boundary after one, so only the line around the offset needs decoding. The
+// offset has to be on a char boundary.
+pub fn is_grapheme_boundary_str(s: &str, offset: usize) -> bool {
+    let line = Line::around(s, offset);
+    is_grapheme_boundary(&line.code_points, line.index(offset))
+}
+
+pub fn next_grapheme_boundary_str(s: &str, offset: usize) -> usize {
+    let line = Line::around(s, offset);
+    line.offset(next_grapheme_boundary(
+        &line.code_points,
+        line.index(offset),
+    ))
+}
+
+pub fn prev_grapheme_boundary_str(s: &str, offset: usize) -> usize {
+    let line = Line::before(s, offset);
+    line.offset(prev_grapheme_boundary(
+        &line.code_points,
+        line.index(offset),
+    ))
+}
+
+// Where backspace at offset should delete back to. Usually editors only delete the last code
+// point, so that typing a wrong accent or Indic vowel sign can be undone without retyping the
+// letter. But that doesn't work for things that are only meaningful as a whole, so it deletes the
+// whole cluster for CR LF, emoji sequences, flags and keycaps, and Hangul syllables made of
+// conjoining jamo. The offset has to be on a char boundary.
+pub fn delete_backward_boundary(text: &str, offset: usize) -> usize {
+    use GraphemeClusterBreak::*;
+    if offset == 0 {
+        return 0;
+    }
+    let start = prev_grapheme_boundary_str(text, offset);
+    let cluster = &text[start..offset];
+    let whole = cluster == "\r\n"
+        || cluster.chars().any(|c| {
+            let cp = c as u32;
+            extended_pictorial(cp)
+                || matches!(cp, 0xFE0F | 0x20E3)
+                || matches!(grapheme_cluster_break(cp), RI | L | V | T | LV | LVT)
+        });
+    if whole {
+        start
+    } else {
+        offset - cluster.chars().next_back().map_or(0, char::len_utf8)
+    }
+}
+
+// Whether inserting at offset leaves the clusters either side of it whole, i.e. it's on a
+// boundary, so it isn't inside an emoji sequence, between CR and LF, or in the middle of a Hangul
+// syllable. Offsets that aren't on a char boundary aren't safe either.
+pub fn insert_is_boundary_safe(text: &str, offset: usize) -> bool {
+    text.is_char_boundary(offset) && is_grapheme_boundary_str(text, offset)
+}
+
+struct Line {
+    end: usize,
+    code_points: Vec<u32>,
+    offsets: Vec<usize>,
+}
+
+impl Line {
+    // The line that offset is in, from just after the last line feed before it, to just after the
+    // next one.
+    fn around(s: &str, offset: usize) -> Self {
+        Line::new(s, offset, offset)
+    }
+
+    // The same, except if offset is at the start of a line, it's the one before, because that's
+    // where the boundary before offset is.
+    fn before(s: &str, offset: usize) -> Self {
+        Line::new(s, offset.saturating_sub(1), offset)
+    }
+
+    fn new(s: &str, search_from: usize, offset: usize) -> Self {
+        assert!(
+            s.is_char_boundary(offset),
+            "not a char boundary: {}",
+            offset
+        );
+        let bytes = s.as_bytes();
+        let start = bytes[..search_from]
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |i| i + 1);
+        let end = bytes[offset..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(s.len(), |i| offset + i + 1);
+        let (offsets, code_points) = s[start..end]
+            .char_indices()
+            .map(|(i, c)| (start + i, c as u32))
+            .unzip();
+        Line {
+            end,
+            code_points,
+            offsets,
+        }
+    }
+
+    fn index(&self, offset: usize) -> usize {
+        self.offsets.partition_point(|o| *o < offset)
+    }
+
+    fn offset(&self, index: usize) -> usize {
+        self.offsets.get(index).copied().unwrap_or(self.end)
+    }
+}
+
+// Whether there's a boundary between pos - 1 and pos, using the same rules as grapheme_end, but
+// without knowing where the cluster started.
+pub fn is_grapheme_boundary(code_points: &[u32], pos: usize) -> bool {
+    grapheme_boundary_rule(code_points, pos).0
+}
+
+// Whether there's a boundary, and the rule that decided it.
+fn grapheme_boundary_rule(code_points: &[u32], pos: usize) -> (bool, &'static str) {
+    use GraphemeClusterBreak::*;
+    if pos == 0 {
+        return (true, "GB1");
+    }
+    if pos >= code_points.len() {
+        return (true, "GB2");
+    }
+    let prev = grapheme_cluster_break(code_points[pos - 1]);
+    let next = grapheme_cluster_break(code_points[pos]);
+    match GRAPHEME_PAIR_RULES[prev as usize][next as usize] {
+        PairRule::NoBreak(rule) => (false, rule),
+        PairRule::Break(rule) => (true, rule),
+        PairRule::Contextual if is_conjunct_before(code_points, pos) => (false, "GB9c"),
+        PairRule::Contextual if prev == ZWJ && is_ext_pict_zwj_before(code_points, pos) => {
+            (false, "GB11")
+        }
+        PairRule::Contextual if prev == RI && next == RI => {
+            // GB12/13: the regional indicators pair up, so it depends on how many came before.
+            let count = code_points[..pos]
+                .iter()
+                .rev()
+                .take_while(|cp| grapheme_cluster_break(**cp) == RI)
+                .count();
+            (count % 2 == 0, "GB12/13")
+        }
+        PairRule::Contextual => (true, "GB999"),
+    }
+}
+
+// Consonant [Extend Linker]* Linker [Extend Linker]* × Consonant
+fn is_conjunct_before(code_points: &[u32], pos: usize) -> bool {
+    if indic_conjunct_break(code_points[pos]) != IndicConjunctBreak::Consonant {
+        return false;
+    }
+    let mut linked = false;
+    for cp in code_points[..pos].iter().rev() {
+        match indic_conjunct_break(*cp) {
+            IndicConjunctBreak::Linker => linked = true,
+            IndicConjunctBreak::Extend => {}
+            IndicConjunctBreak::Consonant => return linked,
+            IndicConjunctBreak::None => return false,
+        }
+    }
+    false
+}
+
+// \p{Extended_Pictographic} Extend* ZWJ × \p{Extended_Pictographic}
+fn is_ext_pict_zwj_before(code_points: &[u32], pos: usize) -> bool {
+    extended_pictorial(code_points[pos])
+        && code_points[..pos - 1]
+            .iter()
+            .rev()
+            .find(|cp| grapheme_cluster_break(**cp) != GraphemeClusterBreak::EX)
+            .is_some_and(|cp| extended_pictorial(*cp))
+}
+
+// The regional indicators in some text, paired up into flags the same way as the clusters are
+// (GB12 and GB13), i.e. from the start of each run of them. The region is the two letters the
+// indicators stand for, e.g. 🇬🇧 is GB. An odd one out at the end of a run can't be shown as a flag,
+// which an emoji picker or sanitiser might want to know about.
+#[derive(Debug, PartialEq)]
+pub enum RegionalIndicator {
+    Flag { pos: usize, region: [char; 2] },
+    Unpaired { pos: usize, letter: char },
+}
+
+pub struct FlagIter<'a> {
+    code_points: &'a [u32],
+    pos: usize,
+}
+
+impl<'a> FlagIter<'a> {
+    pub fn new(code_points: &'a [u32]) -> Self {
+        FlagIter {
+            code_points,
+            pos: 0,
+        }
+    }
+}
+
+fn regional_indicator_letter(code_point: u32) -> Option<char> {
+    match code_point {
+        0x1F1E6..=0x1F1FF => char::from_u32(code_point - 0x1F1E6 + 'A' as u32),
+        _ => None,
+    }
+}
+
+impl<'a> Iterator for FlagIter<'a> {
+    type Item = RegionalIndicator;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.code_points[self.pos.min(self.code_points.len())..]
+            .iter()
+            .position(|cp| regional_indicator_letter(*cp).is_some())?;
+        let pos = self.pos + offset;
+        let first = regional_indicator_letter(self.code_points[pos]).unwrap();
+        match self
+            .code_points
+            .get(pos + 1)
+            .and_then(|cp| regional_indicator_letter(*cp))
+        {
+            Some(second) => {
+                self.pos = pos + 2;
+                Some(RegionalIndicator::Flag {
+                    pos,
+                    region: [first, second],
+                })
+            }
+            None => {
+                self.pos = pos + 1;
+                Some(RegionalIndicator::Unpaired { pos, letter: first })
+            }
+        }
+    }
+}
+
+// Groups conjoining jamo into syllable blocks, the way an input method builds them up: an L can be
+// followed by more Ls, a V or a syllable, a V or LV by Vs or Ts, and a T or LVT by Ts (GB6–GB8).
+// Anything else is a group on its own. With compose, each block is put into NFC, which turns the
+// modern jamo into precomposed syllables, e.g. ᄒ ᅡ ᆫ → 한. Old jamo and extra ones that don't fit
+// in a syllable, like a second L, are left as they are.
+pub fn group_hangul_syllables(code_points: &[u32], compose: bool) -> Vec<Vec<u32>> {
+    use GraphemeClusterBreak::*;
+    let mut groups: Vec<Vec<u32>> = Vec::new();
+    let mut start = 0;
+    for pos in 1..=code_points.len() {
+        let joined = pos < code_points.len()
+            && matches!(
+                (
+                    grapheme_cluster_break(code_points[pos - 1]),
+                    grapheme_cluster_break(code_points[pos])
+                ),
+                (L, L | V | LV | LVT) | (LV | V, V | T) | (LVT | T, T)
+            );
+        if !joined {
+            let group = &code_points[start..pos];
+            groups.push(if compose {
+                to_nfc(group)
+            } else {
+                group.to_vec()
+            });
+            start = pos;
+        }
+    }
+    groups
+}
+
+// Words are a bit different from graphemes, because a lot of the rules look further than the
+// next code point, both forwards and back. E.g. "can't" is one word, but "can'" is two, so whether
+// to break after the n depends on what comes after the apostrophe. So rather than deciding as it
+// goes, it checks each position for a boundary, looking around as far as it needs to.
+pub struct WordIter<'a> {
+    code_points: &'a [u32],
+    pos: usize,
+    dictionary: Option<&'a dyn WordDictionary>,
+    // Ends of the words still to come from the current dictionary run, last word first.
+    dictionary_ends: Vec<usize>,
+    // Whether the last word came from the dictionary, rather than the rules.
+    from_dictionary: bool,
+}
+
+impl<'a> WordIter<'a> {
+    pub fn new(code_points: &'a [u32]) -> Self {
+        WordIter {
+            code_points,
+            pos: 0,
+            dictionary: None,
+            dictionary_ends: Vec::new(),
+            from_dictionary: false,
+        }
+    }
+
+    // Like new, but splits runs of Thai, Lao, Khmer and Burmese into words using the dictionary,
+    // since those are written without spaces and the UAX #29 rules break them at every letter.
+    pub fn with_dictionary(code_points: &'a [u32], dictionary: &'a dyn WordDictionary) -> Self {
+        WordIter {
+            dictionary: Some(dictionary),
+            ..WordIter::new(code_points)
+        }
+    }
+
+    // Like GraphemeIter::rule, or "dictionary" if the dictionary split the word off.
+    pub fn rule(&self) -> &'static str {
+        match self.from_dictionary {
+            true => "dictionary",
+            false => WordSegmenter.explain(self.code_points, self.pos).1,
+        }
+    }
+}
+
+impl<'a> Iterator for WordIter<'a> {
+    type Item = &'a [u32];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.code_points.len() {
+            return None;
+        }
+        let start = self.pos;
+        if self.dictionary_ends.is_empty() {
+            if let Some(dictionary) = self.dictionary {
+                let run_len = self.code_points[start..]
+                    .iter()
+                    .take_while(|cp| is_dictionary_script(**cp))
+                    .count();
+                if run_len > 0 {
+                    let run = &self.code_points[start..start + run_len];
+                    self.dictionary_ends = dictionary_words(run, dictionary)
+                        .into_iter()
+                        .rev()
+                        .map(|end| start + end)
+                        .collect();
+                }
+            }
+        }
+        self.from_dictionary = !self.dictionary_ends.is_empty();
+        if let Some(end) = self.dictionary_ends.pop() {
+            self.pos = end;
+            return Some(&self.code_points[start..end]);
+        }
+        self.pos += 1;
+        while self.pos < self.code_points.len() && !is_word_boundary(self.code_points, self.pos) {
+            self.pos += 1;
+        }
+        Some(&self.code_points[start..self.pos])
+    }
+}
+
+// Source of words for the dictionary segmenter. The frequencies are only compared with each other,
+// so any scale will do, as long as more common words have higher numbers.
+pub trait WordDictionary {
+    // How common the word is, or None if it isn't a word.
+    fn frequency(&self, word: &[u32]) -> Option<u32>;
+
+    // Whether any word starts with prefix. Used to stop looking for longer words.
+    fn has_prefix(&self, prefix: &[u32]) -> bool;
+}
+
+pub struct FrequencyDictionary {
+    trie: Trie<u32>,
+}
+
+impl FrequencyDictionary {
+    pub fn new<'w>(words: impl IntoIterator<Item = (&'w str, u32)>) -> Self {
+        let kvs = words
+            .into_iter()
+            .map(|(word, freq)| (word.chars().map(|c| c as u32).collect(), freq))
+            .collect();
+        FrequencyDictionary {
+            trie: Trie::from_kvs(kvs),
+        }
+    }
+}
+
+impl WordDictionary for FrequencyDictionary {
+    fn frequency(&self, word: &[u32]) -> Option<u32> {
+        match self.trie.get(word) {
+            TrieMatch::Match(freq) => Some(freq),
+            _ => None,
+        }
+    }
+
+    fn has_prefix(&self, prefix: &[u32]) -> bool {
+        self.trie.get(prefix) != TrieMatch::NoMatch
+    }
+}
+
+// Roughly Line_Break=SA, going by block: Thai, Lao, Myanmar (and its extensions), Khmer and Khmer
+// Symbols. Digits are left to the normal rules, so that numbers still come out whole.
+fn is_dictionary_script(cp: u32) -> bool {
+    let in_block = matches!(
+        cp,
+        0x0E00..=0x0EFF
+            | 0x1000..=0x109F
+            | 0x1780..=0x17FF
+            | 0x19E0..=0x19FF
+            | 0xA9E0..=0xA9FF
+            | 0xAA60..=0xAA7F
+    );
+    in_block && word_break(cp) != WordBreak::NU
+}
+
+// Splits a run into words, only breaking between grapheme clusters. Of all the ways to split it,
+// this picks the one with the fewest clusters not covered by a word, then the fewest words, then
+// the most common words. Returns the end of each word; neighbouring unknown clusters are kept
+// together as one word.
+fn dictionary_words(run: &[u32], dictionary: &dyn WordDictionary) -> Vec<usize> {
+    let mut bounds = vec![0];
+    while *bounds.last().unwrap() < run.len() {
+        bounds.push(next_grapheme_boundary(run, *bounds.last().unwrap()));
+    }
+    // (unknown clusters, words, Reverse(total frequency)), the previous bound, and whether the
+    // word ending here was in the dictionary.
+    type Cost = (usize, usize, Reverse<u64>);
+    let mut best: Vec<Option<(Cost, usize, bool)>> = vec![None; bounds.len()];
+    best[0] = Some(((0, 0, Reverse(0)), 0, true));
+    for i in 0..bounds.len() - 1 {
+        let (unknown, words, Reverse(total)) = match best[i] {
+            Some((cost, _, _)) => cost,
+            None => continue,
+        };
+        let mut relax = |j: usize, cost: Cost, known: bool| {
+            if best[j].is_none_or(|(c, _, _)| cost < c) {
+                best[j] = Some((cost, i, known));
+            }
+        };
+        relax(i + 1, (unknown + 1, words + 1, Reverse(total)), false);
+        for j in i + 1..bounds.len() {
+            let word = &run[bounds[i]..bounds[j]];
+            if !dictionary.has_prefix(word) {
+                break;
+            }
+            if let Some(freq) = dictionary.frequency(word) {
+                relax(j, (unknown, words + 1, Reverse(total + freq as u64)), true);
+            }
+        }
+    }
+    let mut ends = Vec::new();
+    let mut j = bounds.len() - 1;
+    let mut unknown_after = false;
+    while j > 0 {
+        let (_, i, known) = best[j].unwrap();
+        if known || !unknown_after {
+            ends.push(bounds[j]);
+        }
+        unknown_after = !known;
+        j = i;
+    }
+    ends.reverse();
+    ends
+}
+
+// WB4: X (Extend | Format | ZWJ)* → X. Apart from the first few rules, these are treated as part
+// of whatever comes before them, so the other rules have to look past them. Except at the start of
+// the text or after a newline, where there isn't anything for them to attach to.
+fn is_ignorable(wb: WordBreak) -> bool {
+    matches!(wb, WordBreak::Extend | WordBreak::FO | WordBreak::ZWJ)
+}
+
+fn skip_ignorable_back(code_points: &[u32], mut pos: usize) -> usize {
+    while pos > 0
+        && is_ignorable(word_break(code_points[pos]))
+        && !matches!(
+            word_break(code_points[pos - 1]),
+            WordBreak::CR | WordBreak::LF | WordBreak::NL
+        )
+    {
+        pos -= 1;
+    }
+    pos
+}
+
+fn skip_ignorable_forward(code_points: &[u32], pos: usize) -> Option<usize> {
+    (pos..code_points.len()).find(|i| !is_ignorable(word_break(code_points[*i])))
+}
+
+// https://unicode.org/reports/tr29/#Word_Boundary_Rules
+// Whether there's a boundary between pos - 1 and pos.
+fn is_word_boundary(code_points: &[u32], pos: usize) -> bool {
+    word_boundary_rule(code_points, pos).0
+}
+
+fn word_boundary_rule(code_points: &[u32], pos: usize) -> (bool, &'static str) {
+    use WordBreak::*;
+    let before = word_break(code_points[pos - 1]);
+    let right = word_break(code_points[pos]);
+    match (before, right) {
+        (CR, LF) => return (false, "WB3"),
+        (NL | CR | LF, _) => return (true, "WB3a"),
+        (_, NL | CR | LF) => return (true, "WB3b"),
+        (ZWJ, _) if extended_pictorial(code_points[pos]) => return (false, "WB3c"),
+        (WSegSpace, WSegSpace) => return (false, "WB3d"),
+        (_, Extend | FO | ZWJ) => return (false, "WB4"),
+        _ => {}
+    }
+
+    // From here on, the ignorables are skipped over.
+    let prev = skip_ignorable_back(code_points, pos - 1);
+    let left = word_break(code_points[prev]);
+    let left2 = match prev {
+        0 => None,
+        _ => Some(word_break(
+            code_points[skip_ignorable_back(code_points, prev - 1)],
+        )),
+    };
+    let right2 = skip_ignorable_forward(code_points, pos + 1).map(|i| word_break(code_points[i]));
+
+    let ah_letter = |wb: WordBreak| matches!(wb, LE | HL);
+    let mid_num_let_q = |wb: WordBreak| matches!(wb, MB | SQ);
+
+    if ah_letter(left) && ah_letter(right) {
+        return (false, "WB5");
+    }
+    if ah_letter(left) && (right == ML || mid_num_let_q(right)) && right2.is_some_and(ah_letter) {
+        return (false, "WB6");
+    }
+    if left2.is_some_and(ah_letter) && (left == ML || mid_num_let_q(left)) && ah_letter(right) {
+        return (false, "WB7");
+    }
+    if left == HL && right == SQ {
+        return (false, "WB7a");
+    }
+    if left == HL && right == DQ && right2 == Some(HL) {
+        return (false, "WB7b");
+    }
+    if left2 == Some(HL) && left == DQ && right == HL {
+        return (false, "WB7c");
+    }
+    if left == NU && right == NU {
+        return (false, "WB8");
+    }
+    if ah_letter(left) && right == NU {
+        return (false, "WB9");
+    }
+    if left == NU && ah_letter(right) {
+        return (false, "WB10");
+    }
+    if left2 == Some(NU) && (left == MN || mid_num_let_q(left)) && right == NU {
+        return (false, "WB11");
+    }
+    if left == NU && (right == MN || mid_num_let_q(right)) && right2 == Some(NU) {
+        return (false, "WB12");
+    }
+    if left == KA && right == KA {
+        return (false, "WB13");
+    }
+    if (ah_letter(left) || matches!(left, NU | KA | EX)) && right == EX {
+        return (false, "WB13a");
+    }
+    if left == EX && (ah_letter(right) || matches!(right, NU | KA)) {
+        return (false, "WB13b");
+    }
+    if left == RI && right == RI {
+        // WB15/16: regional indicators pair up, so it depends on how many came before.
+        let mut count = 0;
+        let mut i = Some(prev);
+        while let Some(j) = i {
+            if word_break(code_points[j]) != RI {
+                break;
+            }
+            count += 1;
+            i = match j {
+                0 => None,
+                _ => Some(skip_ignorable_back(code_points, j - 1)),
+            };
+        }
+        return (count % 2 == 0, "WB15/16");
+    }
+    (true, "WB999")
+}
+
+// Sentences work the same way as words, checking each position, because the rules look back past
+// any closing brackets and spaces for the full stop, and ahead for a lowercase letter, e.g. in
+// "etc. and so on" the sentence carries on after the full stop.
+pub struct SentenceIter<'a> {
+    code_points: &'a [u32],
+    pos: usize,
+}
+
+impl<'a> SentenceIter<'a> {
+    pub fn new(code_points: &'a [u32]) -> Self {
+        SentenceIter {
+            code_points,
+            pos: 0,
+        }
+    }
+
+    // Like GraphemeIter::rule.
+    pub fn rule(&self) -> &'static str {
+        SentenceSegmenter.explain(self.code_points, self.pos).1
+    }
+}
+
+impl<'a> Iterator for SentenceIter<'a> {
+    type Item = &'a [u32];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.code_points.len() {
+            return None;
+        }
+        let start = self.pos;
+        self.pos += 1;
+        while self.pos < self.code_points.len() && !is_sentence_boundary(self.code_points, self.pos)
+        {
+            self.pos += 1;
+        }
+        Some(&self.code_points[start..self.pos])
+    }
+}
+
+fn is_para_sep(sb: SentenceBreak) -> bool {
+    matches!(
+        sb,
+        SentenceBreak::SE | SentenceBreak::CR | SentenceBreak::LF
+    )
+}
+
+// SB5: X (Extend | Format)* → X, the same as WB4, except it's paragraph separators that they
+// don't attach to.
+fn skip_sentence_ignorable_back(code_points: &[u32], mut pos: usize) -> usize {
+    while pos > 0
+        && matches!(
+            sentence_break(code_points[pos]),
+            SentenceBreak::EX | SentenceBreak::FO
+        )
+        && !is_para_sep(sentence_break(code_points[pos - 1]))
+    {
+        pos -= 1;
+    }
+    pos
+}
+
+// The SATerm Close* Sp* before pos, if there is one. Returns the SATerm, and whether there were
+// any spaces after it.
+fn sentence_term_before(code_points: &[u32], pos: usize) -> Option<(SentenceBreak, bool)> {
+    use SentenceBreak::*;
+    let mut i = Some(skip_sentence_ignorable_back(code_points, pos - 1));
+    let step = |i: &mut Option<usize>, matching: SentenceBreak| -> usize {
+        let mut count = 0;
+        while let Some(j) = *i {
+            if sentence_break(code_points[j]) != matching {
+                break;
+            }
+            count += 1;
+            *i = match j {
+                0 => None,
+                _ => Some(skip_sentence_ignorable_back(code_points, j - 1)),
+            };
+        }
+        count
+    };
+    let spaces = step(&mut i, SP);
+    step(&mut i, CL);
+    match i.map(|j| sentence_break(code_points[j])) {
+        Some(term @ (AT | ST)) => Some((term, spaces > 0)),
+        _ => None,
+    }
+}
+
+// https://unicode.org/reports/tr29/#Sentence_Boundary_Rules
+// Whether there's a boundary between pos - 1 and pos.
+fn is_sentence_boundary(code_points: &[u32], pos: usize) -> bool {
+    sentence_boundary_rule(code_points, pos).0
+}
+
+fn sentence_boundary_rule(code_points: &[u32], pos: usize) -> (bool, &'static str) {
+    use SentenceBreak::*;
+    let before = sentence_break(code_points[pos - 1]);
+    let right = sentence_break(code_points[pos]);
+    match (before, right) {
+        (CR, LF) => return (false, "SB3"),
+        (SE | CR | LF, _) => return (true, "SB4"),
+        (_, EX | FO) => return (false, "SB5"),
+        _ => {}
+    }
+
+    let prev = skip_sentence_ignorable_back(code_points, pos - 1);
+    let left = sentence_break(code_points[prev]);
+    let left2 = match prev {
+        0 => None,
+        _ => Some(sentence_break(
+            code_points[skip_sentence_ignorable_back(code_points, prev - 1)],
+        )),
+    };
+
+    if left == AT && right == NU {
+        return (false, "SB6");
+    }
+    if matches!(left2, Some(UP | LO)) && left == AT && right == UP {
+        return (false, "SB7");
+    }
+    let Some((term, spaces)) = sentence_term_before(code_points, pos) else {
+        return (false, "SB998");
+    };
+    if term == AT {
+        // SB8: ATerm Close* Sp* × ( ¬(OLetter | Upper | Lower | ParaSep | SATerm) )* Lower
+        let next = code_points[pos..]
+            .iter()
+            .map(|cp| sentence_break(*cp))
+            .find(|sb| matches!(sb, LE | UP | LO | SE | CR | LF | AT | ST));
+        if next == Some(LO) {
+            return (false, "SB8");
+        }
+    }
+    if matches!(right, SC | AT | ST) {
+        return (false, "SB8a");
+    }
+    if !spaces && matches!(right, CL | SP | SE | CR | LF) {
+        return (false, "SB9");
+    }
+    if matches!(right, SP | SE | CR | LF) {
+        return (false, "SB10");
+    }
+    (true, "SB11")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineBreakKind {
+    Mandatory, // after a newline, or at the end of the text
+    Allowed,
+}
+
+// LB1: the classes that the rules don't deal with directly get resolved to others. The complex
+// context scripts would need a dictionary to find the breaks, so their letters are treated as
+// AL, and their marks (Mn and Mc, which are Extend and SpacingMark for graphemes) as CM.
+fn line_break_class(code_point: u32) -> LineBreak {
+    use LineBreak::*;
+    match line_break(code_point) {
+        AI | SG | XX => AL,
+        SA => match grapheme_cluster_break(code_point) {
+            GraphemeClusterBreak::EX | GraphemeClusterBreak::SM => CM,
+            _ => AL,
+        },
+        CJ => NS,
+        lb => lb,
+    }
 }
 
-impl<'a> Iterator for GraphemeIter<'a> {
-    type Item = &'a [u32];
+// LB9: X (CM | ZWJ)* → X, where X isn't a space or newline. The class of the code point before
+// end, and its index, looking past any combining marks to what they're attached to. LB10: marks
+// that aren't attached to anything are AL.
+fn line_break_before(code_points: &[u32], end: usize) -> (usize, LineBreak) {
+    use LineBreak::*;
+    let mut i = end - 1;
+    while matches!(line_break_class(code_points[i]), CM | ZWJ) {
+        if i == 0 {
+            return (end - 1, AL);
+        }
+        i -= 1;
+    }
+    match line_break_class(code_points[i]) {
+        BK | CR | LF | NL | SP | ZW if i < end - 1 => (end - 1, AL),
+        lb => (i, lb),
+    }
+}
 
-    // https://unicode.org/reports/tr29/#Grapheme_Cluster_Boundary_Rules
-    // At each char, we decide whether to break or keep going.
-    fn next(&mut self) -> Option<Self::Item> {
-        use GraphemeClusterBreak::*;
-        // It's done.
-        if self.pos >= self.code_points.len() {
-            return None;
+// East_Asian_Width F, W or H, for LB30. The halfwidth ones are the halfwidth forms block and the
+// won sign.
+fn is_east_asian_fwh(code_point: u32) -> bool {
+    east_asian_wide(code_point)
+        || matches!(code_point, 0x20A9 | 0xFF61..=0xFFBE | 0xFFC2..=0xFFDC | 0xFFE8..=0xFFEE)
+}
+
+// https://unicode.org/reports/tr14/#Algorithm
+// Whether the line can be broken between pos - 1 and pos. There's never a break at the start of
+// the text (LB2) and always one at the end (LB3).
+pub fn line_break_at(code_points: &[u32], pos: usize) -> Option<LineBreakKind> {
+    line_break_rule(code_points, pos).0
+}
+
+fn line_break_rule(code_points: &[u32], pos: usize) -> (Option<LineBreakKind>, &'static str) {
+    use LineBreak::*;
+    use LineBreakKind::*;
+    if pos == 0 {
+        return (None, "LB2");
+    }
+    if pos >= code_points.len() {
+        return (Some(Mandatory), "LB3");
+    }
+    let prev = line_break_class(code_points[pos - 1]);
+    let next = line_break_class(code_points[pos]);
+    match (prev, next) {
+        (BK, _) => return (Some(Mandatory), "LB4"),
+        (LF | NL, _) => return (Some(Mandatory), "LB5"),
+        (CR, LF) => return (None, "LB5"),
+        (CR, _) => return (Some(Mandatory), "LB5"),
+        (_, BK | CR | LF | NL) => return (None, "LB6"),
+        (_, SP | ZW) => return (None, "LB7"),
+        _ => {}
+    }
+    let space_start = code_points[..pos]
+        .iter()
+        .rposition(|cp| line_break_class(*cp) != SP)
+        .map_or(0, |i| i + 1);
+    if space_start > 0 && line_break_class(code_points[space_start - 1]) == ZW {
+        return (Some(Allowed), "LB8");
+    }
+    if prev == ZWJ {
+        return (None, "LB8a");
+    }
+    if matches!(next, CM | ZWJ) && prev != SP {
+        return (None, "LB9");
+    }
+
+    // From here on, marks are treated as part of what they're attached to.
+    let (left_index, left) = line_break_before(code_points, pos);
+    let right = match next {
+        CM | ZWJ => AL, // LB10
+        lb => lb,
+    };
+    // The class before any spaces, for the rules with SP* in them.
+    let before_spaces = match space_start {
+        0 => None,
+        _ => Some(line_break_before(code_points, space_start).1),
+    };
+    let left2 = match left_index {
+        0 => None,
+        _ => Some(line_break_before(code_points, left_index).1),
+    };
+
+    if left == WJ || right == WJ {
+        return (None, "LB11");
+    }
+    if left == GL {
+        return (None, "LB12");
+    }
+    if right == GL && !matches!(left, SP | BA | HY) {
+        return (None, "LB12a");
+    }
+    if matches!(right, CL | CP | EX | IS | SY) {
+        return (None, "LB13");
+    }
+    if before_spaces == Some(OP) {
+        return (None, "LB14");
+    }
+    if before_spaces == Some(QU) && right == OP {
+        return (None, "LB15");
+    }
+    if matches!(before_spaces, Some(CL | CP)) && right == NS {
+        return (None, "LB16");
+    }
+    if before_spaces == Some(B2) && right == B2 {
+        return (None, "LB17");
+    }
+    if left == SP {
+        return (Some(Allowed), "LB18");
+    }
+    if left == QU || right == QU {
+        return (None, "LB19");
+    }
+    if left == CB || right == CB {
+        return (Some(Allowed), "LB20");
+    }
+    if matches!(right, BA | HY | NS) || left == BB {
+        return (None, "LB21");
+    }
+    if left2 == Some(HL) && matches!(left, HY | BA) {
+        return (None, "LB21a");
+    }
+    if left == SY && right == HL {
+        return (None, "LB21b");
+    }
+    if right == IN {
+        return (None, "LB22");
+    }
+    let keep_together = match (left, right) {
+        (AL | HL, NU) | (NU, AL | HL) => Some("LB23"),
+        (PR, ID | EB | EM) | (ID | EB | EM, PO) => Some("LB23a"),
+        (PR | PO, AL | HL) | (AL | HL, PR | PO) => Some("LB24"),
+        (CL | CP | NU, PO | PR) | (PO | PR, OP | NU) | (HY | IS | NU | SY, NU) => Some("LB25"),
+        (JL, JL | JV | H2 | H3) | (JV | H2, JV | JT) | (JT | H3, JT) => Some("LB26"),
+        (JL | JV | JT | H2 | H3, PO) | (PR, JL | JV | JT | H2 | H3) => Some("LB27"),
+        (AL | HL, AL | HL) => Some("LB28"),
+        (IS, AL | HL) => Some("LB29"),
+        (AL | HL | NU, OP) if !is_east_asian_fwh(code_points[pos]) => Some("LB30"),
+        (CP, AL | HL | NU) if !is_east_asian_fwh(code_points[left_index]) => Some("LB30"),
+        (RI, RI) => {
+            // LB30a: regional indicators pair up, so there's a break before every other one.
+            let mut count = 1;
+            let mut i = left_index;
+            while i > 0 {
+                let (j, lb) = line_break_before(code_points, i);
+                if lb != RI {
+                    break;
+                }
+                count += 1;
+                i = j;
+            }
+            (count % 2 == 1).then_some("LB30a")
         }
-        // At the last char, so just return that.
-        if self.pos == self.code_points.len() - 1 {
-            let start = self.pos;
-            self.pos += 1;
-            return Some(&self.code_points[start..]);
+        (EB, EM) => Some("LB30b"),
+        (_, EM) => {
+            // LB30b: also unassigned pictographs, which might be emoji bases in the future.
+            let cp = code_points[left_index];
+            (extended_pictorial(cp) && !is_assigned(cp)).then_some("LB30b")
         }
+        _ => None,
+    };
+    match keep_together {
+        Some(rule) => (None, rule),
+        None => (Some(Allowed), "LB31"),
+    }
+}
 
-        let start = self.pos;
-        let mut ri_count = 0;
-        while self.pos < self.code_points.len() - 1 {
-            let cp = grapheme_cluster_break(self.code_points[self.pos]);
-            let next = grapheme_cluster_break(self.code_points[self.pos + 1]);
-            ri_count = if cp == RI { ri_count + 1 } else { 0 };
-            match (cp, next) {
-                (CR, LF) => self.pos += 1, // GB3
-                (CN, _) => break,          // GB4
-                (CR, _) => break,          // GB4
-                (LF, _) => break,          // GB4
-                (_, CN) => break,          // GB5
-                (_, CR) => break,          // GB5
-                (_, LF) => break,          // GB5
-                (L, L) => self.pos += 1,   // GB6
-                (L, V) => self.pos += 1,   // GB6
-                (L, LV) => self.pos += 1,  // GB6
-                (L, LVT) => self.pos += 1, // GB6
-                (LV, V) => self.pos += 1,  // GB7
-                (LV, T) => self.pos += 1,  // GB7
-                (V, V) => self.pos += 1,   // GB7
-                (V, T) => self.pos += 1,   // GB7
-                (LVT, T) => self.pos += 1, // GB8
-                (T, T) => self.pos += 1,   // GB8
-                (_, EX) => self.pos += 1,  // GB9
-                (_, ZWJ) => self.pos += 1, // GB9
-                (_, SM) => self.pos += 1,  // GB9a
-                (PP, _) => self.pos += 1,  // GB9b
-                (ZWJ, XX) if extended_pictorial(self.code_points[self.pos + 1]) && self.pos > 0 => {
-                    // Backtrack to see if the previous cps match this regex
-                    // TODO: do this without backtracking
-                    // \p{Extended_Pictographic} Extend* ZWJ 	× 	\p{Extended_Pictographic}
-                    let mut pos = self.pos - 1;
-                    while pos > 0 && grapheme_cluster_break(self.code_points[pos]) == EX {
-                        pos -= 1;
-                    }
-                    if extended_pictorial(self.code_points[pos]) {
-                        self.pos += 1;
-                    } else {
-                        break;
+// Greedy line wrapping: each line gets as many words as fit in max_columns, going by the width of
+// each grapheme cluster, and a word that's too wide for a line of its own gets split between
+// clusters. Returns the byte range of each line, without the spaces or newline it ends with, since
+// those don't need to fit.
+pub fn wrap(text: &str, max_columns: usize) -> Vec<Range<usize>> {
+    let mut code_points = Vec::new();
+    let mut offsets = Vec::new();
+    for (offset, c) in text.char_indices() {
+        code_points.push(c as u32);
+        offsets.push(offset);
+    }
+    offsets.push(text.len());
+
+    // columns[i] is the width of code_points[..i], counting each cluster at its first code point.
+    let mut columns = vec![0; code_points.len() + 1];
+    let mut cluster_ends = Vec::new();
+    let mut start = 0;
+    while start < code_points.len() {
+        let end = next_grapheme_boundary(&code_points, start);
+        let width = columns[start] + cluster_width(&code_points[start..end]);
+        columns[start + 1..=end].fill(width);
+        cluster_ends.push(end);
+        start = end;
+    }
+
+    let trim_end = |start: usize, mut end: usize| {
+        while end > start
+            && matches!(
+                line_break_class(code_points[end - 1]),
+                LineBreak::SP | LineBreak::BK | LineBreak::CR | LineBreak::LF | LineBreak::NL
+            )
+        {
+            end -= 1;
+        }
+        end
+    };
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    let mut last_fit = None;
+    for pos in 1..=code_points.len() {
+        let kind = match line_break_at(&code_points, pos) {
+            Some(kind) => kind,
+            None => continue,
+        };
+        loop {
+            let end = trim_end(line_start, pos);
+            if columns[end] - columns[line_start] <= max_columns {
+                if kind == LineBreakKind::Mandatory {
+                    // Unless a split has already left nothing for this line.
+                    if line_start < pos {
+                        lines.push(offsets[line_start]..offsets[end]);
                     }
-                } // GB11
-                (RI, RI) if ri_count % 2 == 0 => break, // GB12/3
-                (RI, RI) if ri_count % 2 == 1 => self.pos += 1, // GB12/3
-                _ => break,
+                    line_start = pos;
+                    last_fit = None;
+                } else {
+                    last_fit = Some(pos);
+                }
+                break;
             }
+            let split = match last_fit.take() {
+                Some(fit) => fit,
+                None => {
+                    // Too wide even on its own, so as many clusters as fit, but at least one.
+                    let fitting = cluster_ends.iter().copied().filter(|end| {
+                        *end > line_start
+                            && *end <= pos
+                            && columns[*end] - columns[line_start] <= max_columns
+                    });
+                    fitting.max().unwrap_or_else(|| {
+                        next_grapheme_boundary(&code_points, line_start).min(pos)
+                    })
+                }
+            };
+            lines.push(offsets[line_start]..offsets[trim_end(line_start, split)]);
+            line_start = split;
+        }
+    }
+    lines
+}
+
+// The four kinds of boundary behind one interface, so that code moving around text doesn't need to
+// care which it's moving by. The start and end of the text are always boundaries, even for lines,
+// where LB2 says there's no break at the start, but explain still gives LB2's answer there.
+pub trait Segmenter {
+    // Whether there's a boundary between pos - 1 and pos.
+    fn is_boundary(&self, code_points: &[u32], pos: usize) -> bool {
+        self.explain(code_points, pos).0
+    }
+
+    // The same, along with the id of the rule that decided it, like "GB9" or "WB6".
+    fn explain(&self, code_points: &[u32], pos: usize) -> (bool, &'static str);
+}
+
+pub struct GraphemeSegmenter;
+pub struct WordSegmenter;
+pub struct SentenceSegmenter;
+pub struct LineSegmenter;
+
+impl Segmenter for GraphemeSegmenter {
+    fn is_boundary(&self, code_points: &[u32], pos: usize) -> bool {
+        is_grapheme_boundary(code_points, pos)
+    }
+
+    fn explain(&self, code_points: &[u32], pos: usize) -> (bool, &'static str) {
+        grapheme_boundary_rule(code_points, pos)
+    }
+}
+
+impl Segmenter for WordSegmenter {
+    fn explain(&self, code_points: &[u32], pos: usize) -> (bool, &'static str) {
+        match pos {
+            0 => (true, "WB1"),
+            _ if pos >= code_points.len() => (true, "WB2"),
+            _ => word_boundary_rule(code_points, pos),
         }
+    }
+}
+
+impl Segmenter for SentenceSegmenter {
+    fn explain(&self, code_points: &[u32], pos: usize) -> (bool, &'static str) {
+        match pos {
+            0 => (true, "SB1"),
+            _ if pos >= code_points.len() => (true, "SB2"),
+            _ => sentence_boundary_rule(code_points, pos),
+        }
+    }
+}
+
+impl Segmenter for LineSegmenter {
+    fn is_boundary(&self, code_points: &[u32], pos: usize) -> bool {
+        pos == 0 || line_break_at(code_points, pos).is_some()
+    }
+
+    fn explain(&self, code_points: &[u32], pos: usize) -> (bool, &'static str) {
+        let (kind, rule) = line_break_rule(code_points, pos);
+        (kind.is_some(), rule)
+    }
+}
+
+// Every position in the text, with whether it's a boundary and the rule that decided it, for
+// debugging changes to the data or the rules, e.g. to find out why "a.b" is one word (WB6, WB7).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundaryDecision {
+    pub pos: usize,
+    pub is_boundary: bool,
+    pub rule: &'static str,
+}
+
+pub struct ExplainIter<'a, S: Segmenter> {
+    code_points: &'a [u32],
+    segmenter: S,
+    pos: usize,
+}
+
+impl<'a, S: Segmenter> ExplainIter<'a, S> {
+    pub fn new(code_points: &'a [u32], segmenter: S) -> Self {
+        ExplainIter {
+            code_points,
+            segmenter,
+            pos: 0,
+        }
+    }
+}
+
+impl<'a, S: Segmenter> Iterator for ExplainIter<'a, S> {
+    type Item = BoundaryDecision;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos > self.code_points.len() {
+            return None;
+        }
+        let (is_boundary, rule) = self.segmenter.explain(self.code_points, self.pos);
+        let decision = BoundaryDecision {
+            pos: self.pos,
+            is_boundary,
+            rule,
+        };
         self.pos += 1;
-        return Some(&self.code_points[start..self.pos]);
+        Some(decision)
+    }
+}
+
+// A position in the text that can move to the next or previous boundary from wherever it is, like
+// a text cursor. The position doesn't have to be on a boundary to start with.
+pub struct BoundaryCursor<'a, S: Segmenter> {
+    code_points: &'a [u32],
+    segmenter: S,
+    pos: usize,
+}
+
+impl<'a, S: Segmenter> BoundaryCursor<'a, S> {
+    pub fn new(code_points: &'a [u32], segmenter: S) -> Self {
+        BoundaryCursor {
+            code_points,
+            segmenter,
+            pos: 0,
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    // Moves to pos, or the end if it's past it.
+    pub fn set_position(&mut self, pos: usize) {
+        self.pos = pos.min(self.code_points.len());
+    }
+
+    pub fn is_boundary(&self) -> bool {
+        self.segmenter.is_boundary(self.code_points, self.pos)
+    }
+
+    // Moves to the nearest boundary after the current position, or returns None if it's at the end.
+    pub fn next_boundary(&mut self) -> Option<usize> {
+        let len = self.code_points.len();
+        let next =
+            (self.pos + 1..=len).find(|i| self.segmenter.is_boundary(self.code_points, *i))?;
+        self.pos = next;
+        Some(next)
+    }
+
+    // Moves to the nearest boundary before the current position, or returns None if it's at the
+    // start.
+    pub fn prev_boundary(&mut self) -> Option<usize> {
+        let prev = (0..self.pos)
+            .rev()
+            .find(|i| self.segmenter.is_boundary(self.code_points, *i))?;
+        self.pos = prev;
+        Some(prev)
+    }
+}
+
+// A test case from a break test file that the segmenter got wrong. The boundaries are positions in
+// the code points, as in Segmenter::is_boundary.
+#[derive(Debug, PartialEq)]
+pub struct BreakTestFailure {
+    pub line: usize,
+    pub code_points: Vec<u32>,
+    pub expected: Vec<usize>,
+    pub actual: Vec<usize>,
+}
+
+// Runs a file in the format of GraphemeBreakTest.txt, WordBreakTest.txt, SentenceBreakTest.txt or
+// LineBreakTest.txt against a segmenter, e.g. to check a different version of the data, and returns
+// the cases it got wrong. The start of each case isn't checked, because the segmenters always
+// count it as a boundary, but LineBreakTest.txt doesn't. It reads from anything buffered, e.g. a
+// BufReader around the file. Each line looks like:
+// ÷ 0020 × 0308 ÷ 0020 ÷	#  ÷ [0.2] SPACE (Other) × [9.0] COMBINING DIAERESIS (Extend_FE) ...
+pub fn run_break_test(
+    reader: impl io::BufRead,
+    segmenter: &impl Segmenter,
+) -> io::Result<Vec<BreakTestFailure>> {
+    let mut failures = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let case = line.split('#').next().unwrap().trim();
+        if case.is_empty() {
+            continue;
+        }
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid break test on line {}: {}", i + 1, line),
+            )
+        };
+        let mut code_points = Vec::new();
+        let mut expected = Vec::new();
+        for token in case.split_whitespace() {
+            match token {
+                "÷" => expected.push(code_points.len()),
+                "×" => {}
+                _ => code_points.push(u32::from_str_radix(token, 16).map_err(|_| invalid())?),
+            }
+        }
+        expected.retain(|pos| *pos > 0);
+        let actual: Vec<usize> = (1..=code_points.len())
+            .filter(|pos| segmenter.is_boundary(&code_points, *pos))
+            .collect();
+        if actual != expected {
+            failures.push(BreakTestFailure {
+                line: i + 1,
+                code_points,
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(failures)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SegmentKind {
+    Grapheme,
+    Word,
+}
+
+// Segments text that arrives in chunks, like from a socket, without having to buffer all of it.
+// Only complete segments come out, the ones whose end is certain, and the rest is held back until
+// there's more input, or it's finished. A grapheme boundary only depends on what's before it and
+// the code point after it, so as soon as that's arrived, it's certain. A word boundary can depend
+// on the next code point that isn't Extend or Format as well, e.g. whether the . in "a.b" is
+// followed by a letter, so it has to wait for that. The last segment that came out is kept, since
+// the word rules look back a couple of code points past the boundary. Bytes are checked to be
+// valid UTF-8, and an incomplete character at the end of a chunk is held back, like in NfcReader.
+pub struct StreamingSegmenter {
+    kind: SegmentKind,
+    pending: Vec<u8>,
+    offset: usize,
+    code_points: Vec<u32>,
+    start: usize,
+}
+
+impl StreamingSegmenter {
+    pub fn new(kind: SegmentKind) -> Self {
+        StreamingSegmenter {
+            kind,
+            pending: Vec::new(),
+            offset: 0,
+            code_points: Vec::new(),
+            start: 0,
+        }
+    }
+
+    pub fn push(&mut self, code_points: &[u32]) -> Vec<Vec<u32>> {
+        self.code_points.extend_from_slice(code_points);
+        let mut segments = Vec::new();
+        let mut last_start = None;
+        for pos in self.start + 1..self.code_points.len() {
+            if !self.is_certain(pos) {
+                break;
+            }
+            if self.is_boundary(pos) {
+                segments.push(self.code_points[self.start..pos].to_vec());
+                last_start = Some(self.start);
+                self.start = pos;
+            }
+        }
+        if let Some(keep_from) = last_start {
+            self.code_points.drain(..keep_from);
+            self.start -= keep_from;
+        }
+        segments
+    }
+
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> io::Result<Vec<Vec<u32>>> {
+        self.pending.extend_from_slice(bytes);
+        let complete = complete_len(&self.pending);
+        let code_points = decode_utf8(&self.pending[..complete])
+            .map_err(|(err, pos)| invalid_utf8(err, self.offset + pos))?;
+        self.offset += complete;
+        self.pending.drain(..complete);
+        Ok(self.push(&code_points))
+    }
+
+    // Once the input has ended, the boundaries that were waiting to see what came next are
+    // certain, and the end is a boundary too, so the rest all comes out.
+    pub fn finish(&mut self) -> io::Result<Vec<Vec<u32>>> {
+        if !self.pending.is_empty() {
+            return Err(invalid_utf8(DecodeErr::IncompleteCharacter, self.offset));
+        }
+        let mut segments = Vec::new();
+        for pos in self.start + 1..=self.code_points.len() {
+            if pos == self.code_points.len() || self.is_boundary(pos) {
+                segments.push(self.code_points[self.start..pos].to_vec());
+                self.start = pos;
+            }
+        }
+        self.code_points.clear();
+        self.start = 0;
+        Ok(segments)
+    }
+
+    fn is_certain(&self, pos: usize) -> bool {
+        match self.kind {
+            SegmentKind::Grapheme => true,
+            SegmentKind::Word => skip_ignorable_forward(&self.code_points, pos + 1).is_some(),
+        }
+    }
+
+    fn is_boundary(&self, pos: usize) -> bool {
+        match self.kind {
+            SegmentKind::Grapheme => is_grapheme_boundary(&self.code_points, pos),
+            SegmentKind::Word => is_word_boundary(&self.code_points, pos),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::chars::{from_chars, to_string};
+    use crate::helpers::encode_utf8;
 
     // https://www.unicode.org/Public/UCD/latest/ucd/auxiliary/GraphemeBreakTest.txt
+    // (and WordBreakTest.txt and SentenceBreakTest.txt, which are in the same format)
     // The test cases are already split, but we can test the code by concatentating them, and then
     // checking that my split is the same as the original.
     // ÷ 0020 ÷ 0020 ÷	#  ÷ [0.2] SPACE (Other) ÷ [999.0] SPACE (Other) ÷ [0.3]
@@ -110,8 +1787,8 @@ mod tests {
             .collect()
     }
 
-    fn load_test_cases() -> Vec<Vec<Vec<u32>>> {
-        std::fs::read_to_string(std::path::Path::new("resources/GraphemeBreakTest.txt"))
+    fn load_test_cases(path: &str) -> Vec<Vec<Vec<u32>>> {
+        std::fs::read_to_string(std::path::Path::new(path))
             .unwrap()
             .split("\n")
             .filter(|line| !line.is_empty() && !line.starts_with("#"))
@@ -121,10 +1798,748 @@ mod tests {
 
     #[test]
     fn test_grapheme_iter() {
-        for expected in load_test_cases() {
+        for expected in load_test_cases("resources/GraphemeBreakTest.txt") {
+            let to_split = expected.concat();
+            let actual: Vec<&[u32]> = GraphemeIter::new(&to_split).collect();
+            assert_eq!(&actual, &expected);
+        }
+    }
+
+    #[test]
+    fn test_graphemes() {
+        let s = "e\u{0301}a\r\nक्त";
+        assert_eq!(
+            Graphemes::new(s).collect::<Vec<&str>>(),
+            ["e\u{0301}", "a", "\r\n", "क्त"]
+        );
+        assert_eq!(
+            GraphemeIndices::new(s).collect::<Vec<(usize, &str)>>(),
+            [(0, "e\u{0301}"), (3, "a"), (4, "\r\n"), (6, "क्त")]
+        );
+        assert_eq!(Graphemes::new("").next(), None);
+        // the same clusters as the code point version
+        for expected in load_test_cases("resources/GraphemeBreakTest.txt") {
+            let to_split = to_string(&expected.concat()).unwrap();
+            let actual: Vec<Vec<u32>> = Graphemes::new(&to_split)
+                .map(|g| from_chars(g.chars()))
+                .collect();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_grapheme_iter_rev() {
+        for expected in load_test_cases("resources/GraphemeBreakTest.txt") {
+            let to_split = expected.concat();
+            let mut actual: Vec<&[u32]> = GraphemeIter::new(&to_split).rev().collect();
+            actual.reverse();
+            assert_eq!(&actual, &expected);
+        }
+        let s = "e\u{0301}a\r\nक्त";
+        assert_eq!(
+            Graphemes::new(s).rev().collect::<Vec<&str>>(),
+            ["क्त", "\r\n", "a", "e\u{0301}"]
+        );
+        assert_eq!(
+            GraphemeIndices::new(s)
+                .rev()
+                .collect::<Vec<(usize, &str)>>(),
+            [(6, "क्त"), (4, "\r\n"), (3, "a"), (0, "e\u{0301}")]
+        );
+        // from both ends at once
+        let mut graphemes = Graphemes::new(s);
+        assert_eq!(graphemes.next(), Some("e\u{0301}"));
+        assert_eq!(graphemes.next_back(), Some("क्त"));
+        assert_eq!(graphemes.next_back(), Some("\r\n"));
+        assert_eq!(graphemes.next(), Some("a"));
+        assert_eq!(graphemes.next(), None);
+        assert_eq!(graphemes.next_back(), None);
+    }
+
+    #[test]
+    fn test_cluster_width() {
+        assert_eq!(str_width("abc"), 3);
+        assert_eq!(str_width(""), 0);
+        // Combining acute.
+        assert_eq!(str_width("e\u{301}"), 1);
+        assert_eq!(str_width("日本語"), 6);
+        assert_eq!(str_width("ＡＢ"), 4);
+        // Conjoining jamo, which make up one syllable.
+        assert_eq!(str_width("\u{1100}\u{1161}\u{11A8}"), 2);
+        assert_eq!(str_width("한국"), 4);
+        assert_eq!(str_width("a\tb\r\n"), 2);
+        assert_eq!(str_width("\u{200D}"), 0);
+        // ☺ is text by default, ⌚ is emoji.
+        assert_eq!(str_width("\u{263A}"), 1);
+        assert_eq!(str_width("\u{231A}"), 2);
+        assert_eq!(str_width("\u{263A}\u{FE0F}"), 2);
+        assert_eq!(str_width("\u{231A}\u{FE0E}"), 1);
+        assert_eq!(str_width("क्ष"), 1);
+        // Emoji outside the BMP, so checked as code points.
+        assert_eq!(cluster_width(&[0x1F600]), 2);
+        assert_eq!(cluster_width(&[0x1F44D, 0x1F3FD]), 2);
+        assert_eq!(cluster_width(&[0x1F3FD]), 2);
+        assert_eq!(
+            cluster_width(&[0x1F468, 0x200D, 0x1F469, 0x200D, 0x1F467]),
+            2
+        );
+        assert_eq!(cluster_width(&[0x1F1EC, 0x1F1E7]), 2);
+        assert_eq!(cluster_width(&[]), 0);
+    }
+
+    #[test]
+    fn test_line_break_at() {
+        let breaks = |s: &str| -> Vec<String> {
+            let cps = from_chars(s.chars());
+            let mut start = 0;
+            let mut lines = Vec::new();
+            for pos in 1..=cps.len() {
+                if line_break_at(&cps, pos).is_some() {
+                    lines.push(to_string(&cps[start..pos]).unwrap());
+                    start = pos;
+                }
+            }
+            lines
+        };
+        assert_eq!(
+            breaks("The quick (\"brown\") fox can't jump 32.3 feet, right?"),
+            [
+                "The ",
+                "quick ",
+                "(\"brown\") ",
+                "fox ",
+                "can't ",
+                "jump ",
+                "32.3 ",
+                "feet, ",
+                "right?"
+            ]
+        );
+        assert_eq!(
+            breaks("well-known $3.50 50% a/b"),
+            ["well-", "known ", "$3.50 ", "50% ", "a/", "b"]
+        );
+        // Every ideograph can start a line, but not a closing mark.
+        assert_eq!(breaks("日本語。です"), ["日", "本", "語。", "で", "す"]);
+        assert_eq!(breaks("no\u{A0}break"), ["no\u{A0}break"]);
+        assert_eq!(breaks("e\u{301}t\u{E9}"), ["e\u{301}t\u{E9}"]);
+        assert_eq!(breaks("a\r\nb\nc"), ["a\r\n", "b\n", "c"]);
+        assert_eq!(breaks("a\u{200B}b"), ["a\u{200B}", "b"]);
+
+        let cps = [0x61, 0x0A, 0x62];
+        assert_eq!(line_break_at(&cps, 0), None);
+        assert_eq!(line_break_at(&cps, 1), None);
+        assert_eq!(line_break_at(&cps, 2), Some(LineBreakKind::Mandatory));
+        assert_eq!(line_break_at(&cps, 3), Some(LineBreakKind::Mandatory));
+        // Regional indicators pair up into flags.
+        let flags = [0x1F1EC, 0x1F1E7, 0x1F1EB, 0x1F1F7, 0x1F1E9];
+        let breaks: Vec<usize> = (1..5)
+            .filter(|i| line_break_at(&flags, *i).is_some())
+            .collect();
+        assert_eq!(breaks, [2, 4]);
+        // Emoji modifier.
+        assert_eq!(line_break_at(&[0x1F44D, 0x1F3FD], 1), None);
+    }
+
+    #[test]
+    fn test_wrap() {
+        let wrapped = |s: &str, max: usize| -> Vec<String> {
+            wrap(s, max).into_iter().map(|r| s[r].to_string()).collect()
+        };
+        assert_eq!(
+            wrapped("The quick brown fox jumps over the lazy dog.", 10),
+            ["The quick", "brown fox", "jumps over", "the lazy", "dog."]
+        );
+        assert_eq!(wrapped("one two\n\nthree", 20), ["one two", "", "three"]);
+        // Too long for one line, so it's split wherever it has to be.
+        assert_eq!(wrapped("abcdefghij kl", 4), ["abcd", "efgh", "ij", "kl"]);
+        // Wide characters take two columns each.
+        assert_eq!(wrapped("日本語のテキスト", 6), ["日本語", "のテキ", "スト"]);
+        assert_eq!(
+            wrapped("e\u{301}e\u{301}e\u{301} x", 3),
+            ["e\u{301}e\u{301}e\u{301}", "x"]
+        );
+        // Trailing spaces don't count.
+        assert_eq!(wrapped("abc   def", 3), ["abc", "def"]);
+        assert_eq!(wrapped("abc", 0), ["a", "b", "c"]);
+        assert!(wrap("", 10).is_empty());
+    }
+
+    #[test]
+    fn test_boundary_cursor() {
+        fn boundaries<S: Segmenter>(cps: &[u32], segmenter: S) -> (Vec<usize>, Vec<usize>) {
+            let mut cursor = BoundaryCursor::new(cps, segmenter);
+            let forwards: Vec<usize> = std::iter::from_fn(|| cursor.next_boundary()).collect();
+            assert_eq!(cursor.position(), cps.len());
+            let backwards: Vec<usize> = std::iter::from_fn(|| cursor.prev_boundary()).collect();
+            (forwards, backwards)
+        }
+        let cps = from_chars("Hi, e\u{301}ve. Ok".chars());
+        assert_eq!(
+            boundaries(&cps, GraphemeSegmenter),
+            (
+                vec![1, 2, 3, 4, 6, 7, 8, 9, 10, 11, 12],
+                vec![11, 10, 9, 8, 7, 6, 4, 3, 2, 1, 0]
+            )
+        );
+        assert_eq!(
+            boundaries(&cps, WordSegmenter),
+            (vec![2, 3, 4, 8, 9, 10, 12], vec![10, 9, 8, 4, 3, 2, 0])
+        );
+        assert_eq!(
+            boundaries(&cps, SentenceSegmenter),
+            (vec![10, 12], vec![10, 0])
+        );
+        assert_eq!(
+            boundaries(&cps, LineSegmenter),
+            (vec![4, 10, 12], vec![10, 4, 0])
+        );
+
+        // From the middle of a cluster.
+        let mut cursor = BoundaryCursor::new(&cps, GraphemeSegmenter);
+        cursor.set_position(5);
+        assert!(!cursor.is_boundary());
+        assert_eq!(cursor.prev_boundary(), Some(4));
+        cursor.set_position(5);
+        assert_eq!(cursor.next_boundary(), Some(6));
+        cursor.set_position(100);
+        assert_eq!(cursor.position(), 12);
+        assert_eq!(cursor.next_boundary(), None);
+
+        let mut empty = BoundaryCursor::new(&[], WordSegmenter);
+        assert!(empty.is_boundary());
+        assert_eq!(empty.next_boundary(), None);
+        assert_eq!(empty.prev_boundary(), None);
+    }
+
+    #[test]
+    fn test_grapheme_utf16_indices() {
+        let s = "a\u{1F600}e\u{301}\u{1F1EC}\u{1F1E7}!";
+        let expected = [
+            (0, "a"),
+            (1, "\u{1F600}"),
+            (3, "e\u{301}"),
+            (5, "\u{1F1EC}\u{1F1E7}"),
+            (9, "!"),
+        ];
+        assert_eq!(GraphemeUtf16Indices::new(s).collect::<Vec<_>>(), expected);
+        let mut rev: Vec<(usize, &str)> = GraphemeUtf16Indices::new(s).rev().collect();
+        rev.reverse();
+        assert_eq!(rev, expected);
+        // Meeting in the middle.
+        let mut indices = GraphemeUtf16Indices::new(s);
+        assert_eq!(indices.next(), Some((0, "a")));
+        assert_eq!(indices.next_back(), Some((9, "!")));
+        assert_eq!(indices.next_back(), Some((5, "\u{1F1EC}\u{1F1E7}")));
+        assert_eq!(indices.next(), Some((1, "\u{1F600}")));
+        assert_eq!(indices.next(), Some((3, "e\u{301}")));
+        assert_eq!(indices.next(), None);
+        assert_eq!(indices.next_back(), None);
+    }
+
+    #[test]
+    fn test_grapheme_slice() {
+        let s = "ae\u{301}\r\nक्तz";
+        assert_eq!(grapheme_slice(s, 0..5), s);
+        assert_eq!(grapheme_slice(s, 1..3), "e\u{301}\r\n");
+        assert_eq!(grapheme_slice(s, 3..4), "क्त");
+        assert_eq!(grapheme_slice(s, 4..100), "z");
+        assert_eq!(grapheme_slice(s, 5..6), "");
+        assert_eq!(grapheme_slice(s, 2..2), "");
+        let (start, end) = (3, 1);
+        assert_eq!(grapheme_slice(s, start..end), "");
+        assert_eq!(grapheme_slice("", 0..1), "");
+    }
+
+    #[test]
+    fn test_editing_boundaries() {
+        let delete = |s: &str| -> String { s[..delete_backward_boundary(s, s.len())].to_string() };
+        // Just the accent.
+        assert_eq!(delete("ae\u{301}"), "ae");
+        assert_eq!(delete("क्त"), "क्");
+        assert_eq!(delete("a\r\n"), "a");
+        assert_eq!(delete("a\n"), "a");
+        // A syllable in conjoining jamo goes all at once, like a precomposed one.
+        assert_eq!(delete("a\u{1100}\u{1161}\u{11A8}"), "a");
+        assert_eq!(delete("a한"), "a");
+        assert_eq!(delete("x\u{2764}\u{FE0F}"), "x");
+        assert_eq!(delete("x1\u{FE0F}\u{20E3}"), "x");
+        assert_eq!(delete(""), "");
+        let s = "ab\r\nc";
+        assert_eq!(delete_backward_boundary(s, 2), 1);
+
+        assert!(insert_is_boundary_safe(s, 0));
+        assert!(insert_is_boundary_safe(s, 2));
+        assert!(!insert_is_boundary_safe(s, 3));
+        assert!(insert_is_boundary_safe(s, 4));
+        assert!(insert_is_boundary_safe(s, 5));
+        let s = "e\u{301}\u{1100}\u{1161}";
+        assert!(!insert_is_boundary_safe(s, 1));
+        // Inside the UTF-8 of the accent.
+        assert!(!insert_is_boundary_safe(s, 2));
+        assert!(insert_is_boundary_safe(s, 3));
+        assert!(!insert_is_boundary_safe(s, 6));
+        assert!(insert_is_boundary_safe(s, s.len()));
+    }
+
+    #[test]
+    fn test_flag_iter() {
+        use RegionalIndicator::*;
+        // GB, FR, then a lone D, then US after a space, and an odd one out at the end.
+        let cps = [
+            0x1F1EC, 0x1F1E7, 0x1F1EB, 0x1F1F7, 0x1F1E9, 0x20, 0x1F1FA, 0x1F1F8, 0x1F1FA, 0x61,
+        ];
+        assert_eq!(
+            FlagIter::new(&cps).collect::<Vec<_>>(),
+            [
+                Flag {
+                    pos: 0,
+                    region: ['G', 'B']
+                },
+                Flag {
+                    pos: 2,
+                    region: ['F', 'R']
+                },
+                Unpaired {
+                    pos: 4,
+                    letter: 'D'
+                },
+                Flag {
+                    pos: 6,
+                    region: ['U', 'S']
+                },
+                Unpaired {
+                    pos: 8,
+                    letter: 'U'
+                },
+            ]
+        );
+        // The same pairs as the clusters.
+        let flags = cps[..5].to_vec();
+        let clusters: Vec<&[u32]> = GraphemeIter::new(&flags).collect();
+        assert_eq!(clusters, [&cps[0..2], &cps[2..4], &cps[4..5]]);
+        assert_eq!(FlagIter::new(&[0x61, 0x62]).next(), None);
+        assert_eq!(FlagIter::new(&[]).next(), None);
+    }
+
+    #[test]
+    fn test_group_hangul_syllables() {
+        let cps = [
+            0x1112, 0x1161, 0x11AB, // ᄒ ᅡ ᆫ
+            0x1100, 0x1161, // ᄀ ᅡ
+            0x1100, 0x1100, 0x1161, // ᄀ ᄀ ᅡ
+            0xAC00, 0x11A8, // 가 ᆨ
+            0x61, 0x1161, // a ᅡ
+        ];
+        assert_eq!(
+            group_hangul_syllables(&cps, false),
+            [
+                vec![0x1112, 0x1161, 0x11AB],
+                vec![0x1100, 0x1161],
+                vec![0x1100, 0x1100, 0x1161],
+                vec![0xAC00, 0x11A8],
+                vec![0x61],
+                vec![0x1161],
+            ]
+        );
+        assert_eq!(
+            group_hangul_syllables(&cps, true),
+            [
+                vec![0xD55C],
+                vec![0xAC00],
+                vec![0x1100, 0xAC00],
+                vec![0xAC01],
+                vec![0x61],
+                vec![0x1161],
+            ]
+        );
+        assert!(group_hangul_syllables(&[], true).is_empty());
+    }
+
+    #[test]
+    fn test_iter_rules() {
+        let cps = from_chars("e\u{301}\r\n".chars());
+        let mut graphemes = GraphemeIter::new(&cps);
+        let mut rules = Vec::new();
+        while graphemes.next().is_some() {
+            rules.push(graphemes.rule());
+        }
+        assert_eq!(rules, ["GB5", "GB2"]);
+
+        let cps = from_chars("a.b 1".chars());
+        let mut words = WordIter::new(&cps);
+        let mut rules = Vec::new();
+        while words.next().is_some() {
+            rules.push(words.rule());
+        }
+        assert_eq!(rules, ["WB999", "WB999", "WB2"]);
+
+        let cps = from_chars("ไปกิน rice".chars());
+        let dictionary = FrequencyDictionary::new([("ไป", 100), ("กิน", 80)]);
+        let mut words = WordIter::with_dictionary(&cps, &dictionary);
+        let mut rules = Vec::new();
+        while words.next().is_some() {
+            rules.push(words.rule());
+        }
+        assert_eq!(rules, ["dictionary", "dictionary", "WB999", "WB2"]);
+
+        let cps = from_chars("Hi. Ok".chars());
+        let mut sentences = SentenceIter::new(&cps);
+        let mut rules = Vec::new();
+        while sentences.next().is_some() {
+            rules.push(sentences.rule());
+        }
+        assert_eq!(rules, ["SB11", "SB2"]);
+    }
+
+    #[test]
+    fn test_explain_iter() {
+        let explain = |s: &str, segmenter: &dyn Fn(&[u32]) -> Vec<BoundaryDecision>| {
+            let cps = from_chars(s.chars());
+            segmenter(&cps)
+                .into_iter()
+                .map(|d| (d.is_boundary, d.rule))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            explain("e\u{301}\r\n", &|cps| ExplainIter::new(
+                cps,
+                GraphemeSegmenter
+            )
+            .collect()),
+            [
+                (true, "GB1"),
+                (false, "GB9"),
+                (true, "GB5"),
+                (false, "GB3"),
+                (true, "GB2")
+            ]
+        );
+        assert_eq!(
+            explain("a.b 1", &|cps| ExplainIter::new(cps, WordSegmenter)
+                .collect()),
+            [
+                (true, "WB1"),
+                (false, "WB6"),
+                (false, "WB7"),
+                (true, "WB999"),
+                (true, "WB999"),
+                (true, "WB2")
+            ]
+        );
+        assert_eq!(
+            explain("Hi. Ok", &|cps| ExplainIter::new(cps, SentenceSegmenter)
+                .collect()),
+            [
+                (true, "SB1"),
+                (false, "SB998"),
+                (false, "SB998"),
+                (false, "SB9"),
+                (true, "SB11"),
+                (false, "SB998"),
+                (true, "SB2")
+            ]
+        );
+        assert_eq!(
+            explain("a (b", &|cps| ExplainIter::new(cps, LineSegmenter)
+                .collect()),
+            [
+                (false, "LB2"),
+                (false, "LB7"),
+                (true, "LB18"),
+                (false, "LB14"),
+                (true, "LB3")
+            ]
+        );
+        // There's no break at the start, but it's still where the first line starts.
+        assert!(LineSegmenter.is_boundary(&[0x61], 0));
+        // The decisions are the same as the boundaries.
+        for expected in load_test_cases("resources/WordBreakTest.txt") {
+            let cps = expected.concat();
+            for d in ExplainIter::new(&cps, WordSegmenter) {
+                assert_eq!(d.is_boundary, WordSegmenter.is_boundary(&cps, d.pos));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lazy_graphemes() {
+        for expected in load_test_cases("resources/GraphemeBreakTest.txt") {
+            let bytes: Vec<u8> = expected
+                .concat()
+                .into_iter()
+                .flat_map(encode_utf8)
+                .collect();
+            let actual: Vec<Vec<u32>> = ByteGraphemes::new(&bytes)
+                .unwrap()
+                .map(|g| CodePointIter::new(g).collect())
+                .collect();
+            assert_eq!(actual, expected);
+        }
+        let s = "e\u{0301}a\r\nक्त\u{1F1EC}\u{1F1E7}";
+        assert_eq!(
+            LazyGraphemes::new(s).collect::<Vec<&str>>(),
+            Graphemes::new(s).collect::<Vec<&str>>()
+        );
+        assert_eq!(LazyGraphemes::new("").next(), None);
+        assert_eq!(ByteGraphemes::new(b"").unwrap().next(), None);
+        assert_eq!(
+            ByteGraphemes::new(b"a\x80").err(),
+            Some((DecodeErr::UnexpectedContinuation, 1))
+        );
+        assert_eq!(
+            ByteGraphemes::new(b"a\xE2").err(),
+            Some((DecodeErr::IncompleteCharacter, 1))
+        );
+    }
+
+    #[test]
+    fn test_truncate_graphemes() {
+        let s = "e\u{0301}\u{1F469}\u{200D}\u{1F469}\u{200D}\u{1F467}क्तx";
+        assert_eq!(grapheme_count(s), 4);
+        assert_eq!(grapheme_count(""), 0);
+        assert_eq!(truncate_graphemes(s, 0), "");
+        assert_eq!(truncate_graphemes(s, 1), "e\u{0301}");
+        assert_eq!(
+            truncate_graphemes(s, 2),
+            "e\u{0301}\u{1F469}\u{200D}\u{1F469}\u{200D}\u{1F467}"
+        );
+        assert_eq!(truncate_graphemes(s, 4), s);
+        assert_eq!(truncate_graphemes(s, 10), s);
+    }
+
+    #[test]
+    fn test_grapheme_boundaries() {
+        // e + acute, a, CR LF, b
+        let cps = vec![0x65, 0x0301, 0x61, 0x0D, 0x0A, 0x62];
+        let boundaries: Vec<usize> = (0..=cps.len())
+            .filter(|i| is_grapheme_boundary(&cps, *i))
+            .collect();
+        assert_eq!(boundaries, [0, 2, 3, 5, 6]);
+        assert_eq!(next_grapheme_boundary(&cps, 0), 2);
+        assert_eq!(next_grapheme_boundary(&cps, 1), 2);
+        assert_eq!(next_grapheme_boundary(&cps, 3), 5);
+        assert_eq!(next_grapheme_boundary(&cps, 6), 6);
+        assert_eq!(prev_grapheme_boundary(&cps, 5), 3);
+        assert_eq!(prev_grapheme_boundary(&cps, 4), 3);
+        assert_eq!(prev_grapheme_boundary(&cps, 1), 0);
+        assert_eq!(prev_grapheme_boundary(&cps, 0), 0);
+
+        let s = "ab\ne\u{0301}क्त\r\nx";
+        assert!(is_grapheme_boundary_str(s, 3));
+        assert!(!is_grapheme_boundary_str(s, 4));
+        assert_eq!(next_grapheme_boundary_str(s, 3), 6);
+        assert_eq!(next_grapheme_boundary_str(s, 6), 15);
+        assert_eq!(prev_grapheme_boundary_str(s, 15), 6);
+        assert_eq!(next_grapheme_boundary_str(s, 15), 17);
+        assert_eq!(prev_grapheme_boundary_str(s, 17), 15);
+        assert_eq!(prev_grapheme_boundary_str(s, 3), 2);
+        assert_eq!(next_grapheme_boundary_str(s, s.len()), s.len());
+    }
+
+    #[test]
+    fn test_grapheme_iter_zwj() {
+        // 👩‍👩‍👧 is one cluster
+        let family = vec![0x1F469, 0x200D, 0x1F469, 0x200D, 0x1F467];
+        assert_eq!(GraphemeIter::new(&family).count(), 1);
+        // with a skin tone modifier (Extend) before the ZWJ
+        let skin_tone = vec![0x1F469, 0x1F3FD, 0x200D, 0x1F4BB];
+        assert_eq!(GraphemeIter::new(&skin_tone).count(), 1);
+        // but a ZWJ after a letter doesn't join the pictograph on
+        let letter = vec![0x61, 0x200D, 0x1F469];
+        let actual: Vec<&[u32]> = GraphemeIter::new(&letter).collect();
+        assert_eq!(actual, [&[0x61, 0x200D][..], &[0x1F469]]);
+    }
+
+    #[test]
+    fn test_grapheme_iter_conjuncts() {
+        // क्त, ka + virama + ta
+        assert_eq!(GraphemeIter::new(&vec![0x0915, 0x094D, 0x0924]).count(), 1);
+        // क्ष्म, with two viramas
+        let two = vec![0x0915, 0x094D, 0x0937, 0x094D, 0x092E];
+        assert_eq!(GraphemeIter::new(&two).count(), 1);
+        // a nukta or a ZWJ can come between them
+        let nukta = vec![0x0915, 0x093C, 0x094D, 0x200D, 0x0924];
+        assert_eq!(GraphemeIter::new(&nukta).count(), 1);
+        // Bengali ক্ষ
+        assert_eq!(GraphemeIter::new(&vec![0x0995, 0x09CD, 0x09B7]).count(), 1);
+        // without the virama they're separate
+        assert_eq!(GraphemeIter::new(&vec![0x0915, 0x0924]).count(), 2);
+        // and a vowel sign ends it
+        assert_eq!(
+            GraphemeIter::new(&vec![0x0915, 0x093F, 0x094D, 0x0924]).count(),
+            2
+        );
+    }
+
+    // Feeds each test case in one code point at a time, which is the worst case for holding back.
+    #[test]
+    fn test_streaming_segmenter() {
+        let cases = [
+            (SegmentKind::Grapheme, "resources/GraphemeBreakTest.txt"),
+            (SegmentKind::Word, "resources/WordBreakTest.txt"),
+        ];
+        for (kind, path) in cases {
+            for expected in load_test_cases(path) {
+                let mut segmenter = StreamingSegmenter::new(kind);
+                let mut actual = Vec::new();
+                for cp in expected.concat() {
+                    actual.extend(segmenter.push(&[cp]));
+                }
+                actual.extend(segmenter.finish().unwrap());
+                assert_eq!(actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_streaming_segmenter_bytes() {
+        let s = "can't e\u{0301}a.b 3.5";
+        let mut segmenter = StreamingSegmenter::new(SegmentKind::Word);
+        let mut words = Vec::new();
+        // one byte at a time splits the é in the middle
+        for byte in s.as_bytes() {
+            words.extend(segmenter.push_bytes(&[*byte]).unwrap());
+        }
+        words.extend(segmenter.finish().unwrap());
+        let words: Vec<String> = words.iter().map(|w| to_string(w).unwrap()).collect();
+        assert_eq!(words, ["can't", " ", "e\u{0301}a.b", " ", "3.5"]);
+
+        let mut segmenter = StreamingSegmenter::new(SegmentKind::Grapheme);
+        assert!(segmenter.push_bytes(&[0x61, 0xC3]).is_ok());
+        assert!(segmenter.finish().is_err());
+        let mut segmenter = StreamingSegmenter::new(SegmentKind::Grapheme);
+        assert!(segmenter.push_bytes(&[0x80]).is_err());
+    }
+
+    #[test]
+    fn test_word_iter_conformance() {
+        for expected in load_test_cases("resources/WordBreakTest.txt") {
+            let to_split = expected.concat();
+            let actual: Vec<&[u32]> = WordIter::new(&to_split).collect();
+            assert_eq!(&actual, &expected);
+        }
+    }
+
+    #[test]
+    fn test_sentence_iter_conformance() {
+        for expected in load_test_cases("resources/SentenceBreakTest.txt") {
             let to_split = expected.concat();
-            let actual: Vec<&[u32]> = GraphemeIter::new(&to_split).into_iter().collect();
+            let actual: Vec<&[u32]> = SentenceIter::new(&to_split).collect();
             assert_eq!(&actual, &expected);
         }
     }
+
+    #[test]
+    fn test_run_break_test() {
+        let files = [
+            "resources/GraphemeBreakTest.txt",
+            "resources/WordBreakTest.txt",
+            "resources/SentenceBreakTest.txt",
+            "resources/LineBreakTest.txt",
+        ];
+        let open = |path| io::BufReader::new(std::fs::File::open(path).unwrap());
+        assert_eq!(
+            run_break_test(open(files[0]), &GraphemeSegmenter).unwrap(),
+            []
+        );
+        assert_eq!(run_break_test(open(files[1]), &WordSegmenter).unwrap(), []);
+        assert_eq!(
+            run_break_test(open(files[2]), &SentenceSegmenter).unwrap(),
+            []
+        );
+        // The published LineBreakTest.txt tests a tailored LB25 that keeps whole numbers like
+        // "$(12.35)" together, which LineSegmenter doesn't do, so this is the one ICU4X generates
+        // with the untailored rule. It's from 15.0, but every line still passes with the 14.0
+        // data, so none are skipped.
+        assert_eq!(run_break_test(open(files[3]), &LineSegmenter).unwrap(), []);
+
+        // The wrong segmenter for the file.
+        let failures = run_break_test(open(files[1]), &GraphemeSegmenter).unwrap();
+        assert!(!failures.is_empty());
+        let cases = "# comment\n\n÷ 0061 × 0062 ÷\t# ab\n÷ 0061 ÷ 0020 ÷\n".as_bytes();
+        assert_eq!(run_break_test(cases, &WordSegmenter).unwrap(), []);
+        assert_eq!(
+            run_break_test(cases, &LineSegmenter).unwrap(),
+            [BreakTestFailure {
+                line: 4,
+                code_points: vec![0x61, 0x20],
+                expected: vec![1, 2],
+                actual: vec![2],
+            }]
+        );
+        let err = run_break_test("÷ 0061 × zz ÷\n".as_bytes(), &WordSegmenter).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // Lines that aren't UTF-8 are an error too.
+        assert!(run_break_test(&b"\xF7 0061 \xF7\n"[..], &WordSegmenter).is_err());
+    }
+
+    #[test]
+    fn test_sentence_iter() {
+        let sentences = |s: &str| -> Vec<String> {
+            let cps = from_chars(s.chars());
+            SentenceIter::new(&cps)
+                .map(|w| to_string(w).unwrap())
+                .collect()
+        };
+        assert_eq!(
+            sentences("Mr. Smith went to Washington. He said \"hi!\" (Twice.) etc. and so on"),
+            [
+                "Mr. ",
+                "Smith went to Washington. ",
+                "He said \"hi!\" ",
+                "(Twice.) etc. and so on"
+            ]
+        );
+        assert_eq!(
+            sentences("It's 3.14. Yes.\nNo"),
+            ["It's 3.14. ", "Yes.\n", "No"]
+        );
+        assert!(sentences("").is_empty());
+    }
+
+    #[test]
+    fn test_word_iter() {
+        let words = |s: &str| -> Vec<String> {
+            let cps = from_chars(s.chars());
+            WordIter::new(&cps).map(|w| to_string(w).unwrap()).collect()
+        };
+        assert_eq!(
+            words("The quick (\"brown\") fox can't jump 32.3 feet, right?"),
+            [
+                "The", " ", "quick", " ", "(", "\"", "brown", "\"", ")", " ", "fox", " ", "can't",
+                " ", "jump", " ", "32.3", " ", "feet", ",", " ", "right", "?"
+            ]
+        );
+        assert!(words("").is_empty());
+    }
+
+    #[test]
+    fn test_word_iter_dictionary() {
+        let dictionary = FrequencyDictionary::new([
+            ("ไป", 100),
+            ("กิน", 80),
+            ("ข้าว", 90),
+            ("ตา", 50),
+            ("กลม", 20),
+            ("ตาก", 30),
+            ("ลม", 60),
+        ]);
+        let words = |s: &str| -> Vec<String> {
+            let cps = from_chars(s.chars());
+            WordIter::with_dictionary(&cps, &dictionary)
+                .map(|w| to_string(w).unwrap())
+                .collect()
+        };
+        assert_eq!(words("ไปกินข้าว"), ["ไป", "กิน", "ข้าว"]);
+        // Both are two words, but ตาก + ลม are more common.
+        assert_eq!(words("ตากลม"), ["ตาก", "ลม"]);
+        // Unknown clusters stick together.
+        assert_eq!(words("ไปสสกิน"), ["ไป", "สส", "กิน"]);
+        assert_eq!(
+            words("I ไปกิน 2 ข้าว."),
+            ["I", " ", "ไป", "กิน", " ", "2", " ", "ข้าว", "."]
+        );
+        assert!(words("").is_empty());
+    }
 }
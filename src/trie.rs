@@ -1,10 +1,11 @@
 use crate::trie::TrieMatch::PartialMatch;
 
 // TODO: Incomplete implementation of an AdaptiveTrie. It should use arrays for
-// the nodes that have 255 keys. As it is, it's still fast enough to do
-// the partial matches to test the collation algorithm.
+// the dense nodes. As it is, each node keeps its keys sorted by code point so
+// they can be binary searched, which is fast enough to do the partial matches
+// to test the collation algorithm.
 pub struct Trie<T: Clone> {
-    keys: Vec<u8>,
+    keys: Vec<u32>,
     nodes: Vec<Trie<T>>,
     val: Option<T>,
 }
@@ -20,28 +21,27 @@ impl<T: Clone> Trie<T> {
     pub fn from_kvs(pairs: Vec<(Vec<u32>, T)>) -> Self {
         let mut root = Trie::empty();
         for (k, v) in pairs {
-            let key = key_chain(&k);
             let mut node: &mut Trie<T> = &mut root;
-            for part in key.iter() {
-                if let Some(idx) = node.keys.iter_mut().position(|x| x == part) {
-                    node = &mut node.nodes[idx];
-                } else {
-                    node.keys.push(*part);
-                    node.nodes.push(Trie::empty());
-                    let new_len = node.nodes.len();
-                    node = &mut node.nodes[new_len - 1];
-                }
+            for part in k.iter() {
+                let idx = match node.keys.binary_search(part) {
+                    Ok(idx) => idx,
+                    Err(idx) => {
+                        node.keys.insert(idx, *part);
+                        node.nodes.insert(idx, Trie::empty());
+                        idx
+                    }
+                };
+                node = &mut node.nodes[idx];
             }
             node.val = Some(v);
         }
         root
     }
 
-    pub fn get(&self, k: &Vec<u32>) -> TrieMatch<T> {
-        let key = key_chain(k);
+    pub fn get(&self, k: &[u32]) -> TrieMatch<T> {
         let mut node = self;
-        for part in key.iter() {
-            if let Some(idx) = node.keys.iter().position(|x| x == part) {
+        for part in k.iter() {
+            if let Ok(idx) = node.keys.binary_search(part) {
                 node = &node.nodes[idx];
             } else {
                 return TrieMatch::NoMatch;
@@ -62,13 +62,6 @@ impl<T: Clone> Trie<T> {
     }
 }
 
-fn key_chain(k: &Vec<u32>) -> Vec<u8> {
-    k.iter().fold(Vec::new(), |mut acc, n| {
-        acc.extend(n.to_ne_bytes());
-        acc
-    })
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -76,12 +69,22 @@ mod tests {
     #[test]
     fn test_trie_get() {
         let trie = Trie::from_kvs(vec![(vec![0x0, 0xFF], "Cas"), (vec![0xABC, 0xDEF], "Luna")]);
-        assert_eq!(trie.get(&vec![0x0, 0xFF]), TrieMatch::Match("Cas"));
-        assert_eq!(trie.get(&vec![0xABC, 0xDEF]), TrieMatch::Match("Luna"));
+        assert_eq!(trie.get(&[0x0, 0xFF]), TrieMatch::Match("Cas"));
+        assert_eq!(trie.get(&[0xABC, 0xDEF]), TrieMatch::Match("Luna"));
 
-        assert_eq!(trie.get(&vec![0x0]), TrieMatch::PartialMatch);
-        assert_eq!(trie.get(&vec![0xABC]), TrieMatch::PartialMatch);
+        assert_eq!(trie.get(&[0x0]), TrieMatch::PartialMatch);
+        assert_eq!(trie.get(&[0xABC]), TrieMatch::PartialMatch);
 
-        assert_eq!(trie.get(&vec![0xDEF]), TrieMatch::NoMatch);
+        assert_eq!(trie.get(&[0xDEF]), TrieMatch::NoMatch);
+    }
+
+    #[test]
+    fn test_trie_shared_bytes() {
+        // These share their leading byte, but they are different code points.
+        let trie = Trie::from_kvs(vec![(vec![0x0100], "a"), (vec![0x0200, 0x0300], "b")]);
+        assert_eq!(trie.get(&[0x0100]), TrieMatch::Match("a"));
+        assert_eq!(trie.get(&[0x0200]), TrieMatch::PartialMatch);
+        assert_eq!(trie.get(&[0x0300]), TrieMatch::NoMatch);
+        assert_eq!(trie.get(&[0x0200, 0x0300]), TrieMatch::Match("b"));
     }
 }
@@ -31,6 +31,38 @@ lazy_static! {
         serde_json::from_reader(rdr).unwrap()
     };
 
+    // The compatibility forms' equivalents. The Maybes are the same as for NFC, because it's the
+    // same composition, but a lot more code points are No.
+    // cat ucd.all.flat.xml | grep 'NFKC_QC="M"' | grep -Eo 'cp="([0-9A-F]+)"'
+    static ref NFKC_QC_M: HashSet<u32> = {
+        let f = std::fs::File::open("resources/nfkc-quick-check-maybe.json").unwrap();
+        let rdr = std::io::BufReader::new(f);
+        serde_json::from_reader(rdr).unwrap()
+    };
+
+    // cat ucd.all.flat.xml | grep 'NFKC_QC="N"' | grep -Eo 'cp="([0-9A-F]+)"'
+    static ref NFKC_QC_N: HashSet<u32> = {
+        let f = std::fs::File::open("resources/nfkc-quick-check-no.json").unwrap();
+        let rdr = std::io::BufReader::new(f);
+        serde_json::from_reader(rdr).unwrap()
+    };
+
+    // cat ucd.all.flat.xml | grep 'NFKD_QC="N"' | grep -Eo 'cp="([0-9A-F]+)"'
+    static ref NFKD_QC_N: HashSet<u32> = {
+        let f = std::fs::File::open("resources/nfkd-quick-check-no.json").unwrap();
+        let rdr = std::io::BufReader::new(f);
+        serde_json::from_reader(rdr).unwrap()
+    };
+
+    // Ranges of unassigned code points, as [first, last]. There are too many to list them one by
+    // one. Includes the noncharacters, but not surrogates or private use.
+    // cat ucd.all.flat.xml | grep 'gc="Cn"'
+    static ref UNASSIGNED: Vec<[u32; 2]> = {
+        let f = std::fs::File::open("resources/unassigned.json").unwrap();
+        let rdr = std::io::BufReader::new(f);
+        serde_json::from_reader(rdr).unwrap()
+    };
+
     // D114 For any given version of the Unicode Standard, the list of primary composites
     // can be computed by extracting all canonical decomposable characters (dt=can) from
     // UnicodeData.txt in the Unicode Character Database, adding the list of precom-
@@ -44,6 +76,13 @@ lazy_static! {
         HashMap::from_iter(pairs.into_iter())
     };
 
+    // cat ucd.all.flat.xml | grep 'Comp_Ex="Y"' | grep -Eo 'cp="([0-9A-F]+)"'
+    static ref COMPOSITION_EXCLUSIONS: HashSet<u32> = {
+        let f = std::fs::File::open("resources/composition-exclusions.json").unwrap();
+        let rdr = std::io::BufReader::new(f);
+        serde_json::from_reader(rdr).unwrap()
+    };
+
     // Canonical (dt=can) decomposition mappings. Unlike the composite mappings, they include
     // the composition exclusions.
     // cat ucd.all.flat.xml | grep 'dt="can"'
@@ -53,6 +92,30 @@ lazy_static! {
         serde_json::from_reader(rdr).unwrap()
     };
 
+    // The compatibility (dt != can) decomposition mappings, without their type tags.
+    // cat ucd.all.flat.xml | grep -v 'dt="can"' | grep -v 'dt="none"'
+    static ref COMPATIBILITY_DECOMPOSITION_MAPPINGS: HashMap<u32, Vec<u32>> = {
+        let f = std::fs::File::open("resources/compatibility-decomposition-mappings.json").unwrap();
+        let rdr = std::io::BufReader::new(f);
+        serde_json::from_reader(rdr).unwrap()
+    };
+
+    // The type tags that go with the compatibility mappings.
+    // cat ucd.all.flat.xml | grep -v 'dt="can"' | grep -v 'dt="none"' | grep -Eo 'dt="([a-z]+)"'
+    static ref DECOMPOSITION_TYPES: HashMap<u32, DecompositionType> = {
+        let f = std::fs::File::open("resources/decomposition-types.json").unwrap();
+        let rdr = std::io::BufReader::new(f);
+        serde_json::from_reader(rdr).unwrap()
+    };
+
+    // The characters with U+0345 COMBINING GREEK YPOGEGRAMMENI somewhere in their canonical
+    // decomposition. Case folding them without decomposing first gives the wrong result (D145).
+    static ref CONTAINS_YPOGEGRAMMENI: HashSet<u32> = DECOMPOSITION_MAPPINGS
+        .keys()
+        .copied()
+        .filter(|cp| decomposition_contains(*cp, 0x0345))
+        .collect();
+
     static ref COMBINING_CLASSES: HashMap<u32, u8> = {
         let f = std::fs::File::open("resources/combining-class.json").unwrap();
         let rdr = std::io::BufReader::new(f);
@@ -69,13 +132,23 @@ lazy_static! {
         serde_json::from_reader(rdr).unwrap()
     };
 
-    // excludes 0130, which is the one whose lc is two code points
-    static ref LOWERCASE_MAPPINGS: HashMap<u32, u32> = {
+    // The full lowercase mappings. Only 0130 lowercases to more than one code point.
+    // cat dev/ucd.all.flat.xml | grep -v ' lc="#"' | grep ' lc='
+    static ref LOWERCASE_MAPPINGS: HashMap<u32, Vec<u32>> = {
         let f = std::fs::File::open("resources/lowercase-mappings.json").unwrap();
         let rdr = std::io::BufReader::new(f);
         serde_json::from_reader(rdr).unwrap()
     };
 
+    // The full titlecase mappings, which are mostly the same as the uppercase ones, except for the
+    // digraphs like ǆ, which have a separate titlecase form ǅ, and the ligatures like ﬁ → Fi.
+    // cat dev/ucd.all.flat.xml | grep -v ' tc="#"' | grep ' tc='
+    static ref TITLECASE_MAPPINGS: HashMap<u32, Vec<u32>> = {
+        let f = std::fs::File::open("resources/titlecase-mappings.json").unwrap();
+        let rdr = std::io::BufReader::new(f);
+        serde_json::from_reader(rdr).unwrap()
+    };
+
     // grep 'Cased="Y"' | grep 'Cased='
     static ref CASED: HashSet<u32> = serde_json::from_str(
         &std::fs::read_to_string(std::path::Path::new("resources/cased.json")).unwrap()
@@ -86,15 +159,48 @@ lazy_static! {
         &std::fs::read_to_string(std::path::Path::new("resources/case-ignorable.json")
     ).unwrap()).unwrap();
 
+    // The letters with a dot that goes away when an accent is put above them, like i and j.
+    // grep 'SD="Y"'
+    static ref SOFT_DOTTED: HashSet<u32> = serde_json::from_str(
+        &std::fs::read_to_string(std::path::Path::new("resources/soft-dotted.json")
+    ).unwrap()).unwrap();
+
+    // The conditional mappings from SpecialCasing.txt, the ones with a language or a context in
+    // the last field. The unconditional ones are already in the full mappings above. A code point
+    // can have more than one, e.g. 0307 has one for Lithuanian and one each for Turkish and
+    // Azerbaijani. An empty mapping means the code point is removed.
+    // grep -v '^#' SpecialCasing.txt | grep -E '; [a-zA-Z_ ]+; #'
+    static ref SPECIAL_CASING: HashMap<u32, Vec<SpecialCasing>> = serde_json::from_str(
+        &std::fs::read_to_string(std::path::Path::new("resources/special-casing.json")
+    ).unwrap()).unwrap();
+
     // cat ucd.all.flat.xml | grep -v ' cf="#"' | grep ' cf=' (don't want sfc, simple case folding)
     static ref FULL_CASE_FOLDING: HashMap<u32, Vec<u32>> = serde_json::from_str(
         &std::fs::read_to_string(std::path::Path::new("resources/case-folding.json")
     ).unwrap()).unwrap();
 
+    // The case folding turned around: for each folded string, the code points that fold to it.
+    static ref CASE_FOLDING_SOURCES: HashMap<Vec<u32>, Vec<u32>> = {
+        let mut sources: HashMap<Vec<u32>, Vec<u32>> = HashMap::new();
+        for (code_point, folded) in FULL_CASE_FOLDING.iter() {
+            sources.entry(folded.clone()).or_default().push(*code_point);
+        }
+        sources
+    };
+
+    // Only the code points that don't map to themselves. Some map to nothing, because
+    // NFKC_Casefold also removes default ignorables.
+    // cat ucd.all.flat.xml | grep -v ' NFKC_CF="#"' | grep ' NFKC_CF='
+    static ref NFKC_CASEFOLD: HashMap<u32, Vec<u32>> = serde_json::from_str(
+        &std::fs::read_to_string(std::path::Path::new("resources/nfkc-casefold.json")
+    ).unwrap()).unwrap();
+
     // Doesn't include codepoints whose value is XX, because there are 131350 of those, whereas the
-    // rest only total 14190.
+    // rest only total 14190. Stored as [first, last, value] ranges, which there are only 1356 of,
+    // because segmentation looks up every code point at least twice, and a binary search over
+    // those is quicker than hashing.
     // grep 'GCB='
-    static ref GRAPHEME_CLUSTER_BREAK: HashMap<u32, GraphemeClusterBreak> = serde_json::from_str(
+    static ref GRAPHEME_CLUSTER_BREAK: Vec<(u32, u32, GraphemeClusterBreak)> = serde_json::from_str(
         &std::fs::read_to_string(std::path::Path::new("resources/grapheme-cluster-break.json")
     ).unwrap()).unwrap();
 
@@ -103,30 +209,137 @@ lazy_static! {
         &std::fs::read_to_string(std::path::Path::new("resources/extended-pictorial.json")
     ).unwrap()).unwrap();
 
-    static ref COLLATION_ELEMENTS_MAPPING: Trie<Vec<CollationElement>> = {
+    // Indic_Conjunct_Break is from 15.1, so it isn't in the 14.0 data. It's derived the way
+    // UAX #44 describes, for the six scripts that have it: Linker is InSC=Virama, Consonant is
+    // InSC=Consonant, and Extend is GCB=Extend with a non-zero combining class, or ZWJ.
+    static ref INDIC_CONJUNCT_BREAK: HashMap<u32, IndicConjunctBreak> = serde_json::from_str(
+        &std::fs::read_to_string(std::path::Path::new("resources/indic-conjunct-break.json")
+    ).unwrap()).unwrap();
+
+    // Like the grapheme cluster breaks, leaves out the XX (Other) code points.
+    // grep 'WB='
+    static ref WORD_BREAK: HashMap<u32, WordBreak> = serde_json::from_str(
+        &std::fs::read_to_string(std::path::Path::new("resources/word-break.json")
+    ).unwrap()).unwrap();
+
+    // Stored as [first, last, value] ranges, because most of the values are OLetter, which covers
+    // all the CJK ideographs, so there are 127761 of them. Leaves out the XX (Other) code points.
+    // grep 'SB='
+    static ref SENTENCE_BREAK: Vec<(u32, u32, SentenceBreak)> = serde_json::from_str(
+        &std::fs::read_to_string(std::path::Path::new("resources/sentence-break.json")
+    ).unwrap()).unwrap();
+
+    // Ranges of East_Asian_Width Wide or Fullwidth, as [first, last]. Includes the unassigned
+    // code points in the CJK blocks and planes 2 and 3, which default to Wide.
+    // grep -E 'ea="(W|F)"'
+    static ref EAST_ASIAN_WIDE: Vec<[u32; 2]> = serde_json::from_str(
+        &std::fs::read_to_string(std::path::Path::new("resources/east-asian-wide.json")
+    ).unwrap()).unwrap();
+
+    // Ranges of characters shown as emoji rather than text by default, as [first, last].
+    // grep 'EPres="Y"'
+    static ref EMOJI_PRESENTATION: Vec<[u32; 2]> = serde_json::from_str(
+        &std::fs::read_to_string(std::path::Path::new("resources/emoji-presentation.json")
+    ).unwrap()).unwrap();
+
+    // Stored as [first, last, value] ranges, like the sentence breaks. Leaves out the XX (Unknown)
+    // code points.
+    // grep 'lb='
+    static ref LINE_BREAK: Vec<(u32, u32, LineBreak)> = serde_json::from_str(
+        &std::fs::read_to_string(std::path::Path::new("resources/line-break.json")
+    ).unwrap()).unwrap();
+
+    // Stored as [first, last, value] ranges. Leaves out L, which is the default, but includes the
+    // defaults for unassigned code points in the right-to-left blocks, like R for Hebrew.
+    // grep 'bc='
+    static ref BIDI_CLASS: Vec<(u32, u32, BidiClass)> = serde_json::from_str(
+        &std::fs::read_to_string(std::path::Path::new("resources/bidi-class.json")
+    ).unwrap()).unwrap();
+
+    static ref COLLATION_ELEMENTS_14_0: Trie<Vec<CollationElement>> = {
         let f = std::fs::File::open("resources/collation-elements.json").unwrap();
         let rdr = std::io::BufReader::new(f);
         let pairs: Vec<(Vec<u32>, Vec<CollationElement>)> = serde_json::from_reader(rdr).unwrap();
         Trie::from_kvs(pairs)
     };
 
+    // allkeys.txt from UCA 13.0, for matching collations that haven't moved on to 14.0.
+    static ref COLLATION_ELEMENTS_13_0: Trie<Vec<CollationElement>> = {
+        let f = std::fs::File::open("resources/collation-elements-13.0.json").unwrap();
+        let rdr = std::io::BufReader::new(f);
+        let pairs: Vec<(Vec<u32>, Vec<CollationElement>)> = serde_json::from_reader(rdr).unwrap();
+        Trie::from_kvs(pairs)
+    };
+
     // grep 'UIdeo="Y"'
     static ref UNIFIED_IDEOGRAPHS: HashSet<u32> = serde_json::from_str(
         &std::fs::read_to_string(std::path::Path::new("resources/unified-ideograph.json")
     ).unwrap()).unwrap();
 }
 
+// The tailorings are prebuilt against the 14.0 DUCET, from the CLDR rules written above each one.
+// A new primary can't be squeezed between two existing ones, so a letter that sorts after another
+// gets that letter's element plus one with a primary higher than anything in the DUCET, e.g. ñ is
+// [n][FFFF], which puts it after every string starting with n.
+
+// &AE << ä <<< Ä &OE << ö <<< Ö &UE << ü <<< Ü
+#[cfg(feature = "collation-de-phonebook")]
+lazy_static! {
+    static ref COLLATION_DE_PHONEBOOK: Trie<Vec<CollationElement>> = {
+        let f = std::fs::File::open("resources/collation-de-phonebook.json").unwrap();
+        let rdr = std::io::BufReader::new(f);
+        let pairs: Vec<(Vec<u32>, Vec<CollationElement>)> = serde_json::from_reader(rdr).unwrap();
+        Trie::from_kvs(pairs)
+    };
+}
+
+// &Y << ü <<< Ü &[before 1]ǀ < å <<< Å < ä <<< Ä < ö <<< Ö
+#[cfg(feature = "collation-sv")]
+lazy_static! {
+    static ref COLLATION_SV: Trie<Vec<CollationElement>> = {
+        let f = std::fs::File::open("resources/collation-sv.json").unwrap();
+        let rdr = std::io::BufReader::new(f);
+        let pairs: Vec<(Vec<u32>, Vec<CollationElement>)> = serde_json::from_reader(rdr).unwrap();
+        Trie::from_kvs(pairs)
+    };
+}
+
+// &N < ñ <<< Ñ &C < ch <<< cH <<< Ch <<< CH &L < ll <<< lL <<< Ll <<< LL
+#[cfg(feature = "collation-es-traditional")]
+lazy_static! {
+    static ref COLLATION_ES_TRADITIONAL: Trie<Vec<CollationElement>> = {
+        let f = std::fs::File::open("resources/collation-es-traditional.json").unwrap();
+        let rdr = std::io::BufReader::new(f);
+        let pairs: Vec<(Vec<u32>, Vec<CollationElement>)> = serde_json::from_reader(rdr).unwrap();
+        Trie::from_kvs(pairs)
+    };
+}
+
 pub fn decomposition_mapping(code_point: u32) -> Option<Vec<u32>> {
-    DECOMPOSITION_MAPPINGS
+    DECOMPOSITION_MAPPINGS.get(&code_point).cloned()
+}
+
+// Borrows the mapping from the table, for when it's only read, so it doesn't need a Vec.
+pub fn decomposition_mapping_slice(code_point: u32) -> Option<&'static [u32]> {
+    DECOMPOSITION_MAPPINGS.get(&code_point).map(Vec::as_slice)
+}
+
+pub fn compatibility_decomposition_mapping(code_point: u32) -> Option<Vec<u32>> {
+    COMPATIBILITY_DECOMPOSITION_MAPPINGS
         .get(&code_point)
-        .map(|mapping| mapping.clone())
+        .cloned()
+}
+
+pub fn decomposition_type(code_point: u32) -> Option<DecompositionType> {
+    DECOMPOSITION_TYPES.get(&code_point).copied()
+}
+
+pub fn is_assigned(code_point: u32) -> bool {
+    !in_ranges(&UNASSIGNED, code_point)
 }
 
 pub fn combining_class(code_point: u32) -> u8 {
-    COMBINING_CLASSES
-        .get(&code_point)
-        .map(|ccc| *ccc)
-        .unwrap_or(0)
+    COMBINING_CLASSES.get(&code_point).copied().unwrap_or(0)
 }
 
 pub fn is_starter(code_point: u32) -> bool {
@@ -157,21 +370,49 @@ pub fn is_allowed(code_point: u32, normalisation: &Normalisation) -> QuickCheckV
                 QuickCheckVal::Yes
             }
         }
-        // Normalisation::NFKC => todo!(),
-        // Normalisation::NFKD => todo!(),
+        Normalisation::NFKC => {
+            if NFKC_QC_M.contains(&code_point) {
+                QuickCheckVal::Maybe
+            } else if NFKC_QC_N.contains(&code_point) {
+                QuickCheckVal::No
+            } else {
+                QuickCheckVal::Yes
+            }
+        }
+        Normalisation::NFKD => {
+            if NFKD_QC_N.contains(&code_point) {
+                QuickCheckVal::No
+            } else {
+                QuickCheckVal::Yes
+            }
+        }
     }
 }
 
-pub fn primary_composite(l: u32, c: u32) -> Option<u32> {
-    PRIMARY_COMPOSITES.get(&[l, c]).map(|cp| *cp)
+// The composite that a pair composes to, if any. Includes the Hangul syllables, but not the
+// composition exclusions, since those never get composed.
+pub fn canonical_composition(l: u32, c: u32) -> Option<u32> {
+    PRIMARY_COMPOSITES.get(&[l, c]).copied()
 }
 
-pub fn lowercase_mapping(code_point: u32) -> Option<u32> {
-    LOWERCASE_MAPPINGS.get(&code_point).map(|cp| *cp)
+// Full_Composition_Exclusion, which is the composition exclusions table plus the singletons and
+// the non-starter decompositions.
+pub fn is_composition_exclusion(code_point: u32) -> bool {
+    COMPOSITION_EXCLUSIONS.contains(&code_point)
+}
+
+pub fn lowercase_mapping(code_point: u32) -> Option<Vec<u32>> {
+    LOWERCASE_MAPPINGS.get(&code_point).cloned()
 }
 
 pub fn uppercase_mapping(code_point: u32) -> Option<Vec<u32>> {
-    UPPERCASE_MAPPINGS.get(&code_point).map(|cp| cp.clone())
+    UPPERCASE_MAPPINGS.get(&code_point).cloned()
+}
+
+// The full Titlecase_Mapping (tc), without the conditional ones from SpecialCasing.txt. None if it
+// maps to itself.
+pub fn titlecase_mapping(code_point: u32) -> Option<Vec<u32>> {
+    TITLECASE_MAPPINGS.get(&code_point).cloned()
 }
 
 pub fn cased(code_point: u32) -> bool {
@@ -182,11 +423,94 @@ pub fn case_ignorable(code_point: u32) -> bool {
     CASE_IGNORABLE.contains(&code_point)
 }
 
+pub fn soft_dotted(code_point: u32) -> bool {
+    SOFT_DOTTED.contains(&code_point)
+}
+
+pub fn special_casing(code_point: u32) -> &'static [SpecialCasing] {
+    SPECIAL_CASING
+        .get(&code_point)
+        .map(|entries| entries.as_slice())
+        .unwrap_or(&[])
+}
+
+pub fn contains_ypogegrammeni(code_point: u32) -> bool {
+    CONTAINS_YPOGEGRAMMENI.contains(&code_point)
+}
+
+fn decomposition_contains(code_point: u32, target: u32) -> bool {
+    DECOMPOSITION_MAPPINGS
+        .get(&code_point)
+        .is_some_and(|mapping| {
+            mapping
+                .iter()
+                .any(|cp| *cp == target || decomposition_contains(*cp, target))
+        })
+}
+
 pub fn case_folding(code_point: u32) -> Option<Vec<u32>> {
-    FULL_CASE_FOLDING.get(&code_point).map(|cps| cps.clone())
+    FULL_CASE_FOLDING.get(&code_point).cloned()
+}
+
+// The code points whose case folding is the given string, not counting the string itself if it's a
+// single code point, because that doesn't have a case folding.
+pub fn case_folding_sources(folded: &[u32]) -> Vec<u32> {
+    CASE_FOLDING_SOURCES
+        .get(folded)
+        .cloned()
+        .unwrap_or_default()
+}
+
+pub fn nfkc_casefold(code_point: u32) -> Option<Vec<u32>> {
+    NFKC_CASEFOLD.get(&code_point).cloned()
+}
+
+// One line of SpecialCasing.txt. The language is the ISO 639 code, like "tr", and the context is
+// one of the conditions from Table 3-17, which is negated for Not_Before_Dot.
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub struct SpecialCasing {
+    pub lower: Vec<u32>,
+    pub title: Vec<u32>,
+    pub upper: Vec<u32>,
+    pub language: Option<String>,
+    pub context: Option<CasingContext>,
+    pub negated: bool,
+}
+
+#[derive(Copy, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub enum CasingContext {
+    FinalSigma,
+    AfterSoftDotted,
+    MoreAbove,
+    BeforeDot,
+    AfterI,
+}
+
+// https://www.unicode.org/reports/tr44/#Formatting_Tags_Table
+// Only the compatibility types, canonical mappings don't have a tag.
+#[derive(Copy, Clone, Deserialize, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum DecompositionType {
+    Com,  // otherwise unspecified compatibility character
+    Enc,  // encircled form
+    Fin,  // final presentation form (Arabic)
+    Font, // font variant
+    Fra,  // vulgar fraction form
+    Init, // initial presentation form (Arabic)
+    Iso,  // isolated presentation form (Arabic)
+    Med,  // medial presentation form (Arabic)
+    Nar,  // narrow (or hankaku) compatibility character
+    Nb,   // no-break version of a space or hyphen
+    Sml,  // small variant form (CNS compatibility)
+    Sqr,  // CJK squared font variant
+    Sub,  // subscript form
+    Sup,  // superscript form
+    Vert, // vertical layout presentation form
+    Wide, // wide (or zenkaku) compatibility character
 }
 
 // https://unicode.org/reports/tr29/#Grapheme_Cluster_Break_Property_Values
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Copy, Clone, Deserialize, Debug, PartialEq)]
 pub enum GraphemeClusterBreak {
     CN,  // control char, separator
@@ -210,26 +534,304 @@ pub enum GraphemeClusterBreak {
 }
 
 pub fn grapheme_cluster_break(code_point: u32) -> GraphemeClusterBreak {
-    GRAPHEME_CLUSTER_BREAK
+    range_value(&GRAPHEME_CLUSTER_BREAK, code_point).unwrap_or(GraphemeClusterBreak::XX)
+}
+
+// https://unicode.org/reports/tr29/#Word_Break_Property_Values
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Copy, Clone, Deserialize, Debug, PartialEq)]
+pub enum WordBreak {
+    CR,        // carriage return
+    DQ,        // double quote
+    EX,        // extend num let, connector punctuation like _
+    Extend,    // extend
+    FO,        // format
+    HL,        // hebrew letter
+    KA,        // katakana
+    LE,        // a letter
+    LF,        // line feed
+    MB,        // mid num let, . and the like
+    ML,        // mid letter, : and the like
+    MN,        // mid num, , and ; and the like
+    NL,        // newline
+    NU,        // numeric
+    RI,        // regional indicator
+    SQ,        // single quote
+    WSegSpace, // whitespace
+    XX,        // other
+    ZWJ,       // zero width joiner
+}
+
+pub fn word_break(code_point: u32) -> WordBreak {
+    WORD_BREAK
         .get(&code_point)
-        .map(|gcb| *gcb)
-        .unwrap_or(GraphemeClusterBreak::XX)
+        .copied()
+        .unwrap_or(WordBreak::XX)
+}
+
+// https://unicode.org/reports/tr29/#Sentence_Break_Property_Values
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Copy, Clone, Deserialize, Debug, PartialEq)]
+pub enum SentenceBreak {
+    AT, // a full stop that might be an abbreviation, like .
+    CL, // close, quotes and brackets
+    CR, // carriage return
+    EX, // extend
+    FO, // format
+    LE, // a letter that isn't upper or lower case
+    LF, // line feed
+    LO, // lower
+    NU, // numeric
+    SC, // continues a sentence, like , and :
+    SE, // separator, like the paragraph separator
+    SP, // space
+    ST, // a full stop that always ends a sentence, like ! and ?
+    UP, // upper
+    XX, // other
+}
+
+pub fn sentence_break(code_point: u32) -> SentenceBreak {
+    range_value(&SENTENCE_BREAK, code_point).unwrap_or(SentenceBreak::XX)
+}
+
+// https://unicode.org/reports/tr14/#Table1
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Copy, Clone, Deserialize, Debug, PartialEq)]
+pub enum LineBreak {
+    AI,  // ambiguous, either AL or ID depending on context
+    AL,  // alphabetic
+    B2,  // break opportunity before and after, like the em dash
+    BA,  // break after, like most spaces other than U+0020
+    BB,  // break before
+    BK,  // mandatory break, like the line separator
+    CB,  // contingent break, left up to the embedded object
+    CJ,  // small kana, treated as NS
+    CL,  // close punctuation
+    CM,  // combining mark
+    CP,  // close parenthesis
+    CR,  // carriage return
+    EB,  // emoji base
+    EM,  // emoji modifier
+    EX,  // exclamation and interrogation
+    GL,  // non-breaking glue, like the no-break space
+    H2,  // Hangul LV syllable
+    H3,  // Hangul LVT syllable
+    HL,  // Hebrew letter
+    HY,  // hyphen-minus
+    ID,  // ideographic
+    IN,  // inseparable, like the ellipsis
+    IS,  // infix numeric separator, like . and ,
+    JL,  // Hangul L jamo
+    JT,  // Hangul T jamo
+    JV,  // Hangul V jamo
+    LF,  // line feed
+    NL,  // next line
+    NS,  // nonstarter
+    NU,  // numeric
+    OP,  // open punctuation
+    PO,  // postfix numeric, like %
+    PR,  // prefix numeric, like $
+    QU,  // quotation mark
+    RI,  // regional indicator
+    SA,  // complex context, like Thai, resolved to AL or CM
+    SG,  // surrogate
+    SP,  // space
+    SY,  // symbol allowing a break after, like /
+    WJ,  // word joiner
+    XX,  // unknown
+    ZW,  // zero width space
+    ZWJ, // zero width joiner
+}
+
+pub fn line_break(code_point: u32) -> LineBreak {
+    range_value(&LINE_BREAK, code_point).unwrap_or(LineBreak::XX)
+}
+
+// https://unicode.org/reports/tr9/#Table_Bidirectional_Character_Types
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Copy, Clone, Deserialize, Debug, PartialEq)]
+pub enum BidiClass {
+    AL,  // Arabic letter
+    AN,  // Arabic number
+    B,   // paragraph separator
+    BN,  // boundary neutral, like the format controls
+    CS,  // common number separator, like , and :
+    EN,  // European number
+    ES,  // European number separator, + and -
+    ET,  // European number terminator, like $ and %
+    FSI, // first strong isolate
+    L,   // left to right
+    LRE, // left to right embedding
+    LRI, // left to right isolate
+    LRO, // left to right override
+    NSM, // non-spacing mark
+    ON,  // other neutral
+    PDF, // pop directional format
+    PDI, // pop directional isolate
+    R,   // right to left
+    RLE, // right to left embedding
+    RLI, // right to left isolate
+    RLO, // right to left override
+    S,   // segment separator, like tab
+    WS,  // whitespace
+}
+
+pub fn bidi_class(code_point: u32) -> BidiClass {
+    range_value(&BIDI_CLASS, code_point).unwrap_or(BidiClass::L)
+}
+
+// The value for the range that contains code_point, for the properties stored as
+// [first, last, value] ranges.
+fn range_value<T: Copy>(ranges: &[(u32, u32, T)], code_point: u32) -> Option<T> {
+    ranges
+        .binary_search_by(|(first, last, _)| {
+            if code_point < *first {
+                std::cmp::Ordering::Greater
+            } else if code_point > *last {
+                std::cmp::Ordering::Less
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .ok()
+        .map(|i| ranges[i].2)
+}
+
+fn in_ranges(ranges: &[[u32; 2]], code_point: u32) -> bool {
+    ranges
+        .binary_search_by(|[first, last]| {
+            if code_point < *first {
+                std::cmp::Ordering::Greater
+            } else if code_point > *last {
+                std::cmp::Ordering::Less
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+pub fn east_asian_wide(code_point: u32) -> bool {
+    in_ranges(&EAST_ASIAN_WIDE, code_point)
+}
+
+pub fn emoji_presentation(code_point: u32) -> bool {
+    in_ranges(&EMOJI_PRESENTATION, code_point)
 }
 
 pub fn extended_pictorial(code_point: u32) -> bool {
     EXTENDED_PICTORIAL.contains(&code_point)
 }
 
+#[derive(Copy, Clone, Deserialize, Debug, PartialEq)]
+pub enum IndicConjunctBreak {
+    Consonant,
+    Extend,
+    Linker,
+    None,
+}
+
+pub fn indic_conjunct_break(code_point: u32) -> IndicConjunctBreak {
+    INDIC_CONJUNCT_BREAK
+        .get(&code_point)
+        .copied()
+        .unwrap_or(IndicConjunctBreak::None)
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct CollationElement {
     pub weights: Vec<u16>,
     pub variable: bool,
 }
 
-pub fn collation_elements(code_points: &Vec<u32>) -> TrieMatch<Vec<CollationElement>> {
-    COLLATION_ELEMENTS_MAPPING.get(code_points)
+// Each version of the DUCET is its own table, and only the ones that are used get loaded. To add
+// a version, extract its allkeys.txt to another json file and add a variant here.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum UcaVersion {
+    V13_0,
+    V14_0,
+}
+
+impl UcaVersion {
+    pub const LATEST: UcaVersion = UcaVersion::V14_0;
+}
+
+// Each one is behind its own feature, so only the locales that are needed get shipped.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Tailoring {
+    #[cfg(feature = "collation-de-phonebook")]
+    GermanPhonebook,
+    #[cfg(feature = "collation-sv")]
+    Swedish,
+    #[cfg(feature = "collation-es-traditional")]
+    SpanishTraditional,
+}
+
+fn tailoring_table(tailoring: Tailoring) -> &'static Trie<Vec<CollationElement>> {
+    match tailoring {
+        #[cfg(feature = "collation-de-phonebook")]
+        Tailoring::GermanPhonebook => &COLLATION_DE_PHONEBOOK,
+        #[cfg(feature = "collation-sv")]
+        Tailoring::Swedish => &COLLATION_SV,
+        #[cfg(feature = "collation-es-traditional")]
+        Tailoring::SpanishTraditional => &COLLATION_ES_TRADITIONAL,
+    }
+}
+
+// The tailoring takes precedence, but the match is only partial if neither table has an entry,
+// since the tailored contractions can start with something the DUCET has an entry for.
+pub fn collation_elements(
+    code_points: &[u32],
+    version: UcaVersion,
+    tailoring: Option<Tailoring>,
+) -> TrieMatch<Vec<CollationElement>> {
+    let tailored = tailoring
+        .map(|t| tailoring_table(t).get(code_points))
+        .unwrap_or(TrieMatch::NoMatch);
+    if let TrieMatch::Match(_) = tailored {
+        return tailored;
+    }
+    let untailored = match version {
+        UcaVersion::V13_0 => COLLATION_ELEMENTS_13_0.get(code_points),
+        UcaVersion::V14_0 => COLLATION_ELEMENTS_14_0.get(code_points),
+    };
+    match (untailored, tailored) {
+        (TrieMatch::NoMatch, TrieMatch::PartialMatch) => TrieMatch::PartialMatch,
+        (untailored, _) => untailored,
+    }
 }
 
 pub fn unified_ideograph(code_point: u32) -> bool {
     UNIFIED_IDEOGRAPHS.contains(&code_point)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_composition() {
+        assert_eq!(canonical_composition(0x61, 0x030A), Some(0x00E5));
+        assert_eq!(canonical_composition(0x1100, 0x1161), Some(0xAC00));
+        assert_eq!(canonical_composition(0x61, 0x62), None);
+        // U+0958 decomposes to U+0915 U+093C, but it's excluded
+        assert!(is_composition_exclusion(0x0958));
+        assert_eq!(canonical_composition(0x0915, 0x093C), None);
+        // singleton
+        assert!(is_composition_exclusion(0x212B));
+        assert!(!is_composition_exclusion(0x00E5));
+    }
+
+    #[test]
+    fn test_titlecase_mapping() {
+        // ǆ → ǅ, where the uppercase is Ǆ
+        assert_eq!(titlecase_mapping(0x01C6), Some(vec![0x01C5]));
+        assert_eq!(uppercase_mapping(0x01C6), Some(vec![0x01C4]));
+        assert_eq!(titlecase_mapping(0x01C5), None);
+        // ﬁ → Fi, ß → Ss
+        assert_eq!(titlecase_mapping(0xFB01), Some(vec![0x0046, 0x0069]));
+        assert_eq!(titlecase_mapping(0x00DF), Some(vec![0x0053, 0x0073]));
+        assert_eq!(titlecase_mapping(0x0061), Some(vec![0x0041]));
+        assert_eq!(titlecase_mapping(0x0041), None);
+    }
+}
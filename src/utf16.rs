@@ -0,0 +1,271 @@
+// UTF-16: code points up to U+FFFF are one unit, and the rest are split into a high surrogate
+// (D800–DBFF) and a low one (DC00–DFFF), carrying ten bits each.
+
+use crate::cp_iter::LossyCodePointIter;
+use crate::helpers::{encode_utf8_into, DecodeErr};
+use crate::validate::decode_at;
+
+#[derive(Debug, PartialEq)]
+pub enum Utf16Err {
+    // An odd number of bytes, so the last unit is missing its other half.
+    IncompleteCodeUnit,
+    UnpairedSurrogate,
+}
+
+fn is_high_surrogate(unit: u16) -> bool {
+    (0xD800..=0xDBFF).contains(&unit)
+}
+
+fn is_low_surrogate(unit: u16) -> bool {
+    (0xDC00..=0xDFFF).contains(&unit)
+}
+
+// Errors come with the index of the unit they're at.
+pub fn decode_utf16(units: &[u16]) -> Result<Vec<u32>, (Utf16Err, usize)> {
+    let mut code_points = Vec::with_capacity(units.len());
+    let mut pos = 0;
+    while pos < units.len() {
+        let (code_point, len) = decode_utf16_at(units, pos).map_err(|err| (err, pos))?;
+        code_points.push(code_point);
+        pos += len;
+    }
+    Ok(code_points)
+}
+
+// The code point starting at pos, and how many units it took.
+fn decode_utf16_at(units: &[u16], pos: usize) -> Result<(u32, usize), Utf16Err> {
+    let unit = units[pos];
+    if is_high_surrogate(unit) {
+        match units.get(pos + 1) {
+            Some(&low) if is_low_surrogate(low) => {
+                // 1101_10aa aaaa_aaaa 1101_11bb bbbb_bbbb -> aaaa_aaaa aabb_bbbb_bbbb + 0x10000
+                let high_bits = (unit as u32 & 0x3FF) << 10;
+                Ok((0x10000 + (high_bits | (low as u32 & 0x3FF)), 2))
+            }
+            _ => Err(Utf16Err::UnpairedSurrogate),
+        }
+    } else if is_low_surrogate(unit) {
+        Err(Utf16Err::UnpairedSurrogate)
+    } else {
+        Ok((unit as u32, 1))
+    }
+}
+
+// The byte versions report errors at byte offsets.
+pub fn decode_utf16_be(bytes: &[u8]) -> Result<Vec<u32>, (Utf16Err, usize)> {
+    decode_utf16_bytes(bytes, u16::from_be_bytes)
+}
+
+pub fn decode_utf16_le(bytes: &[u8]) -> Result<Vec<u32>, (Utf16Err, usize)> {
+    decode_utf16_bytes(bytes, u16::from_le_bytes)
+}
+
+fn decode_utf16_bytes(
+    bytes: &[u8],
+    from_bytes: fn([u8; 2]) -> u16,
+) -> Result<Vec<u32>, (Utf16Err, usize)> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err((Utf16Err::IncompleteCodeUnit, bytes.len() - 1));
+    }
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]))
+        .collect();
+    decode_utf16(&units).map_err(|(err, pos)| (err, pos * 2))
+}
+
+// Writes the encoding to the start of buf, and returns how many units it took. Like UTF-8,
+// anything above U+10FFFF is written as U+FFFD, and surrogates are written as they are.
+pub fn encode_utf16_to(code_point: u32, buf: &mut [u16; 2]) -> usize {
+    if code_point < 0x10000 {
+        buf[0] = code_point as u16;
+        1
+    } else if code_point > 0x10FFFF {
+        encode_utf16_to(0xFFFD, buf)
+    } else {
+        let bits = code_point - 0x10000;
+        buf[0] = 0xD800 | (bits >> 10) as u16;
+        buf[1] = 0xDC00 | (bits & 0x3FF) as u16;
+        2
+    }
+}
+
+pub fn encode_utf16(code_points: &[u32]) -> Vec<u16> {
+    let mut units = Vec::with_capacity(code_points.len());
+    let mut buf = [0; 2];
+    for cp in code_points {
+        let len = encode_utf16_to(*cp, &mut buf);
+        units.extend_from_slice(&buf[..len]);
+    }
+    units
+}
+
+pub fn encode_utf16_be(code_points: &[u32]) -> Vec<u8> {
+    encode_utf16(code_points)
+        .into_iter()
+        .flat_map(u16::to_be_bytes)
+        .collect()
+}
+
+pub fn encode_utf16_le(code_points: &[u32]) -> Vec<u8> {
+    encode_utf16(code_points)
+        .into_iter()
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+// Converting straight between UTF-8 and UTF-16, without collecting the code points in between.
+
+pub fn utf8_to_utf16(bytes: &[u8]) -> Result<Vec<u16>, (DecodeErr, usize)> {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut buf = [0; 2];
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let (code_point, len) = decode_at(bytes, pos).map_err(|(err, _)| (err, pos))?;
+        let n = encode_utf16_to(code_point, &mut buf);
+        units.extend_from_slice(&buf[..n]);
+        pos += len;
+    }
+    Ok(units)
+}
+
+// Invalid sequences become U+FFFD, the same way fix replaces them.
+pub fn utf8_to_utf16_lossy(bytes: &[u8]) -> Vec<u16> {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut buf = [0; 2];
+    for code_point in LossyCodePointIter::new(bytes) {
+        let n = encode_utf16_to(code_point, &mut buf);
+        units.extend_from_slice(&buf[..n]);
+    }
+    units
+}
+
+pub fn utf16_to_utf8(units: &[u16]) -> Result<String, (Utf16Err, usize)> {
+    let mut out = Vec::with_capacity(units.len());
+    let mut pos = 0;
+    while pos < units.len() {
+        let (code_point, len) = decode_utf16_at(units, pos).map_err(|err| (err, pos))?;
+        encode_utf8_into(code_point, &mut out);
+        pos += len;
+    }
+    Ok(String::from_utf8(out).unwrap())
+}
+
+// Unpaired surrogates become U+FFFD.
+pub fn utf16_to_utf8_lossy(units: &[u16]) -> String {
+    let mut out = Vec::with_capacity(units.len());
+    let mut pos = 0;
+    while pos < units.len() {
+        match decode_utf16_at(units, pos) {
+            Ok((code_point, len)) => {
+                encode_utf8_into(code_point, &mut out);
+                pos += len;
+            }
+            Err(_) => {
+                encode_utf8_into(0xFFFD, &mut out);
+                pos += 1;
+            }
+        }
+    }
+    String::from_utf8(out).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chars::from_chars;
+
+    #[test]
+    fn test_decode_utf16() {
+        let s = "a\u{E9}\u{20AC}\u{1F600}\u{10FFFF}";
+        let units: Vec<u16> = s.encode_utf16().collect();
+        assert_eq!(decode_utf16(&units), Ok(from_chars(s.chars())));
+        assert_eq!(decode_utf16(&[]), Ok(vec![]));
+
+        // A high surrogate at the end, one followed by something else, and a low one by itself.
+        assert_eq!(
+            decode_utf16(&[0x61, 0xD83D]),
+            Err((Utf16Err::UnpairedSurrogate, 1))
+        );
+        assert_eq!(
+            decode_utf16(&[0xD83D, 0x61]),
+            Err((Utf16Err::UnpairedSurrogate, 0))
+        );
+        assert_eq!(
+            decode_utf16(&[0x61, 0x62, 0xDE00]),
+            Err((Utf16Err::UnpairedSurrogate, 2))
+        );
+    }
+
+    #[test]
+    fn test_decode_utf16_bytes() {
+        assert_eq!(
+            decode_utf16_be(&[0x00, 0x61, 0xD8, 0x3D, 0xDE, 0x00]),
+            Ok(vec![0x61, 0x1F600])
+        );
+        assert_eq!(
+            decode_utf16_le(&[0x61, 0x00, 0x3D, 0xD8, 0x00, 0xDE]),
+            Ok(vec![0x61, 0x1F600])
+        );
+        assert_eq!(
+            decode_utf16_be(&[0x00, 0x61, 0x00]),
+            Err((Utf16Err::IncompleteCodeUnit, 2))
+        );
+        assert_eq!(
+            decode_utf16_le(&[0x61, 0x00, 0x00, 0xDE]),
+            Err((Utf16Err::UnpairedSurrogate, 2))
+        );
+    }
+
+    #[test]
+    fn test_encode_utf16() {
+        for s in [
+            "",
+            "hello",
+            "ศไทย中华Việt Nam",
+            "\u{1F600}x\u{10000}\u{10FFFF}",
+        ] {
+            let units: Vec<u16> = s.encode_utf16().collect();
+            let code_points = from_chars(s.chars());
+            assert_eq!(encode_utf16(&code_points), units);
+            assert_eq!(decode_utf16(&encode_utf16(&code_points)), Ok(code_points));
+        }
+        assert_eq!(encode_utf16(&[0x61, 0x110000, 0x62]), [0x61, 0xFFFD, 0x62]);
+        assert_eq!(encode_utf16(&[u32::MAX]), [0xFFFD]);
+        assert_eq!(
+            encode_utf16_be(&[0x61, 0x1F600]),
+            [0x00, 0x61, 0xD8, 0x3D, 0xDE, 0x00]
+        );
+        assert_eq!(
+            encode_utf16_le(&[0x61, 0x1F600]),
+            [0x61, 0x00, 0x3D, 0xD8, 0x00, 0xDE]
+        );
+    }
+
+    #[test]
+    fn test_utf8_to_utf16() {
+        for s in ["", "hello", "ศไทย中华Việt Nam", "\u{1F600}x\u{10FFFF}"] {
+            let units: Vec<u16> = s.encode_utf16().collect();
+            assert_eq!(utf8_to_utf16(s.as_bytes()), Ok(units.clone()));
+            assert_eq!(utf8_to_utf16_lossy(s.as_bytes()), units);
+            assert_eq!(utf16_to_utf8(&units), Ok(s.to_string()));
+            assert_eq!(utf16_to_utf8_lossy(&units), s);
+        }
+        assert_eq!(
+            utf8_to_utf16(b"ab\xC0\x80"),
+            Err((DecodeErr::OverlongEncoding, 2))
+        );
+        assert_eq!(
+            utf8_to_utf16_lossy(b"a\xC0\x80b\xF0\x9F\x98"),
+            [0x61, 0xFFFD, 0xFFFD, 0x62, 0xFFFD]
+        );
+        assert_eq!(
+            utf16_to_utf8(&[0x61, 0xDE00, 0x62]),
+            Err((Utf16Err::UnpairedSurrogate, 1))
+        );
+        assert_eq!(
+            utf16_to_utf8_lossy(&[0xD83D, 0x61, 0xDE00, 0xD83D, 0xDE00, 0xD83D]),
+            "\u{FFFD}a\u{FFFD}\u{1F600}\u{FFFD}"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+// UTF-32: every code point is one four byte unit, so the only things to check are that there's a
+// whole number of units, and that each one is a code point that can be encoded at all.
+
+use crate::helpers::is_valid_codepoint;
+
+#[derive(Debug, PartialEq)]
+pub enum Utf32Err {
+    // The length isn't a multiple of four, so the last unit is cut off.
+    IncompleteCodeUnit,
+    // Above U+10FFFF, or a surrogate.
+    InvalidCodePoint,
+}
+
+// Errors come with the byte offset of the unit they're at.
+pub fn decode_utf32_be(bytes: &[u8]) -> Result<Vec<u32>, (Utf32Err, usize)> {
+    decode_utf32_bytes(bytes, u32::from_be_bytes)
+}
+
+pub fn decode_utf32_le(bytes: &[u8]) -> Result<Vec<u32>, (Utf32Err, usize)> {
+    decode_utf32_bytes(bytes, u32::from_le_bytes)
+}
+
+fn decode_utf32_bytes(
+    bytes: &[u8],
+    from_bytes: fn([u8; 4]) -> u32,
+) -> Result<Vec<u32>, (Utf32Err, usize)> {
+    let mut code_points = Vec::with_capacity(bytes.len() / 4);
+    let mut chunks = bytes.chunks_exact(4);
+    for (i, chunk) in chunks.by_ref().enumerate() {
+        let code_point = from_bytes(chunk.try_into().unwrap());
+        if !is_valid_codepoint(code_point) {
+            return Err((Utf32Err::InvalidCodePoint, i * 4));
+        }
+        code_points.push(code_point);
+    }
+    if !chunks.remainder().is_empty() {
+        return Err((Utf32Err::IncompleteCodeUnit, bytes.len() / 4 * 4));
+    }
+    Ok(code_points)
+}
+
+pub fn encode_utf32_be(code_points: &[u32]) -> Vec<u8> {
+    code_points.iter().flat_map(|cp| cp.to_be_bytes()).collect()
+}
+
+pub fn encode_utf32_le(code_points: &[u32]) -> Vec<u8> {
+    code_points.iter().flat_map(|cp| cp.to_le_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_utf32() {
+        let be = [0, 0, 0, 0x61, 0, 0x01, 0xF6, 0x00, 0, 0x10, 0xFF, 0xFF];
+        let le = [0x61, 0, 0, 0, 0x00, 0xF6, 0x01, 0, 0xFF, 0xFF, 0x10, 0];
+        assert_eq!(decode_utf32_be(&be), Ok(vec![0x61, 0x1F600, 0x10FFFF]));
+        assert_eq!(decode_utf32_le(&le), Ok(vec![0x61, 0x1F600, 0x10FFFF]));
+        assert_eq!(decode_utf32_be(&[]), Ok(vec![]));
+
+        assert_eq!(
+            decode_utf32_be(&[0, 0, 0, 0x61, 0, 0x11, 0, 0]),
+            Err((Utf32Err::InvalidCodePoint, 4))
+        );
+        assert_eq!(
+            decode_utf32_le(&[0x00, 0xD8, 0, 0]),
+            Err((Utf32Err::InvalidCodePoint, 0))
+        );
+        assert_eq!(
+            decode_utf32_be(&[0, 0, 0, 0x61, 0, 0]),
+            Err((Utf32Err::IncompleteCodeUnit, 4))
+        );
+    }
+
+    #[test]
+    fn test_encode_utf32() {
+        let code_points = [0x61, 0x20AC, 0x1F600];
+        assert_eq!(
+            encode_utf32_be(&code_points),
+            [0, 0, 0, 0x61, 0, 0, 0x20, 0xAC, 0, 0x01, 0xF6, 0x00]
+        );
+        assert_eq!(
+            decode_utf32_be(&encode_utf32_be(&code_points)),
+            Ok(code_points.to_vec())
+        );
+        assert_eq!(
+            decode_utf32_le(&encode_utf32_le(&code_points)),
+            Ok(code_points.to_vec())
+        );
+    }
+}
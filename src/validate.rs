@@ -1,4 +1,5 @@
 use crate::helpers::*;
+use std::ops::Range;
 
 fn bytes_remaining(code_unit: &CodeUnit) -> usize {
     match code_unit {
@@ -22,50 +23,133 @@ fn is_overlong(code_point: u32, code_unit: &CodeUnit) -> bool {
 
 pub fn validate(input: &[u8]) -> Result<(), (DecodeErr, usize)> {
     let mut pos = 0;
-    let len = input.len();
-    while pos < len {
-        let code_unit = CodeUnit::try_from(input[pos]).map_err(|de| (de, pos))?;
-        match code_unit {
-            CodeUnit::SingleByte => {
-                pos += 1;
+    while pos < input.len() {
+        // Most text is mostly ASCII, so runs of it are skipped a word at a time.
+        if input[pos].is_ascii() {
+            pos += ascii_prefix_len(&input[pos..]);
+            continue;
+        }
+        let (_, len) = decode_at(input, pos).map_err(|(de, _)| (de, pos))?;
+        pos += len;
+    }
+    Ok(())
+}
+
+// Validates while decoding, so untrusted bytes don't need a pass to validate them before a
+// CodePointIter can be trusted to decode them.
+pub fn decode_utf8(input: &[u8]) -> Result<Vec<u32>, (DecodeErr, usize)> {
+    let mut code_points = Vec::with_capacity(input.len());
+    let mut pos = 0;
+    while pos < input.len() {
+        if input[pos].is_ascii() {
+            let len = ascii_prefix_len(&input[pos..]);
+            code_points.extend(input[pos..pos + len].iter().map(|b| *b as u32));
+            pos += len;
+            continue;
+        }
+        let (code_point, len) = decode_at(input, pos).map_err(|(de, _)| (de, pos))?;
+        code_points.push(code_point);
+        pos += len;
+    }
+    Ok(code_points)
+}
+
+// Every error rather than just the first, carrying on after each one from where fix would. Each
+// comes with the bytes of its maximal subpart.
+pub fn validate_all(input: &[u8]) -> Vec<(DecodeErr, Range<usize>)> {
+    let mut errors = Vec::new();
+    let mut pos = 0;
+    while pos < input.len() {
+        match decode_at(input, pos) {
+            Ok((_, len)) => pos += len,
+            Err((err, len)) => {
+                errors.push((err, pos..pos + len));
+                pos += len;
             }
-            CodeUnit::Continuation => {
-                return Err((DecodeErr::UnexpectedContinuation, pos));
+        }
+    }
+    errors
+}
+
+// Decodes the code point starting at pos, and returns it along with how many bytes it took. Errors
+// come with the length of the maximal subpart, so the caller knows how much to skip.
+pub fn decode_at(input: &[u8], pos: usize) -> Result<(u32, usize), (DecodeErr, usize)> {
+    decode_code_point(input, pos).map_err(|err| (err, maximal_subpart_len(input, pos)))
+}
+
+fn decode_code_point(input: &[u8], pos: usize) -> Result<(u32, usize), DecodeErr> {
+    let code_unit = CodeUnit::try_from(input[pos])?;
+    match code_unit {
+        CodeUnit::SingleByte => Ok((input[pos] as u32, 1)),
+        CodeUnit::Continuation => Err(DecodeErr::UnexpectedContinuation),
+        _ => {
+            let remaining = bytes_remaining(&code_unit);
+            if pos + remaining >= input.len() {
+                return Err(DecodeErr::IncompleteCharacter);
             }
-            _ => {
-                let remaining = bytes_remaining(&code_unit);
-                if pos + remaining >= len {
-                    return Err((DecodeErr::IncompleteCharacter, pos));
-                }
-                for i in 1..=remaining {
-                    match CodeUnit::try_from(input[pos + i]) {
-                        Ok(CodeUnit::Continuation) => {}
-                        _ => {
-                            return Err((DecodeErr::IncompleteCharacter, pos));
-                        }
-                    }
-                }
-                let code_point = match code_unit {
-                    CodeUnit::DoublePrefix => decode_double(input[pos], input[pos + 1]),
-                    CodeUnit::TriplePrefix => {
-                        decode_triple(input[pos], input[pos + 1], input[pos + 2])
-                    }
-                    CodeUnit::QuadPrefix => {
-                        decode_quad(input[pos], input[pos + 1], input[pos + 2], input[pos + 3])
+            for i in 1..=remaining {
+                match CodeUnit::try_from(input[pos + i]) {
+                    Ok(CodeUnit::Continuation) => {}
+                    _ => {
+                        return Err(DecodeErr::IncompleteCharacter);
                     }
-                    _ => unreachable!(),
-                };
-                if !is_valid_codepoint(code_point) {
-                    return Err((DecodeErr::InvalidCodePoint, pos));
                 }
-                if is_overlong(code_point, &code_unit) {
-                    return Err((DecodeErr::OverlongEncoding, pos));
+            }
+            let code_point = match code_unit {
+                CodeUnit::DoublePrefix => decode_double(input[pos], input[pos + 1]),
+                CodeUnit::TriplePrefix => decode_triple(input[pos], input[pos + 1], input[pos + 2]),
+                CodeUnit::QuadPrefix => {
+                    decode_quad(input[pos], input[pos + 1], input[pos + 2], input[pos + 3])
                 }
-                pos += 1 + remaining;
+                _ => unreachable!(),
+            };
+            if !is_valid_codepoint(code_point) {
+                return Err(DecodeErr::InvalidCodePoint);
             }
+            if is_overlong(code_point, &code_unit) {
+                return Err(DecodeErr::OverlongEncoding);
+            }
+            Ok((code_point, 1 + remaining))
+        }
+    }
+}
+
+// Validates a stream that arrives in chunks, where a character can be split between two of them.
+// A character left unfinished at the end of a chunk isn't an error until finish says the stream
+// is over, whereas anything that couldn't be valid whatever came next is an error straight away.
+// Positions in errors are from the start of the stream.
+#[derive(Default)]
+pub struct Utf8Validator {
+    pending: Vec<u8>,
+    offset: usize,
+}
+
+impl Utf8Validator {
+    pub fn new() -> Self {
+        Utf8Validator::default()
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), (DecodeErr, usize)> {
+        self.pending.extend_from_slice(chunk);
+        let complete = complete_len(&self.pending);
+        validate(&self.pending[..complete]).map_err(|(err, pos)| (err, self.offset + pos))?;
+        self.offset += complete;
+        self.pending.drain(..complete);
+        Ok(())
+    }
+
+    // Whether everything fed so far ends on a character boundary, rather than needing more bytes.
+    pub fn is_complete(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn finish(&self) -> Result<(), (DecodeErr, usize)> {
+        if self.is_complete() {
+            Ok(())
+        } else {
+            Err((DecodeErr::IncompleteCharacter, self.offset))
         }
     }
-    return Ok(());
 }
 
 #[cfg(test)]
@@ -128,4 +212,152 @@ mod tests {
         assert!(validate(&[0xF0, 0x90, 0x80, 0x80]).is_ok());
         assert!(validate(&[0xF4, 0x8F, 0xBF, 0xBF]).is_ok());
     }
+
+    #[test]
+    fn test_decode_at() {
+        let input = "a\u{E9}\u{20AC}\u{1F600}".as_bytes();
+        assert_eq!(decode_at(input, 0), Ok((0x61, 1)));
+        assert_eq!(decode_at(input, 1), Ok((0xE9, 2)));
+        assert_eq!(decode_at(input, 3), Ok((0x20AC, 3)));
+        assert_eq!(decode_at(input, 6), Ok((0x1F600, 4)));
+        assert_eq!(
+            decode_at(input, 2),
+            Err((DecodeErr::UnexpectedContinuation, 1))
+        );
+        assert_eq!(
+            decode_at(&input[..8], 6),
+            Err((DecodeErr::IncompleteCharacter, 2))
+        );
+        assert_eq!(
+            decode_at(b"\xED\xA0\x80", 0),
+            Err((DecodeErr::InvalidCodePoint, 1))
+        );
+    }
+
+    #[test]
+    fn test_utf8_validator() {
+        let bytes = "a\u{E9}\u{20AC}\u{1F600}z".as_bytes();
+        // Every way of splitting it in two.
+        for split in 0..=bytes.len() {
+            let mut validator = Utf8Validator::new();
+            assert_eq!(validator.feed(&bytes[..split]), Ok(()));
+            assert_eq!(validator.feed(&bytes[split..]), Ok(()));
+            assert_eq!(validator.finish(), Ok(()));
+        }
+        // And a byte at a time.
+        let mut validator = Utf8Validator::new();
+        for b in bytes {
+            assert_eq!(validator.feed(&[*b]), Ok(()));
+        }
+        assert!(validator.is_complete());
+
+        // Cut off at the end of the stream.
+        let mut validator = Utf8Validator::new();
+        assert_eq!(validator.feed(b"ab\xF0\x9F"), Ok(()));
+        assert_eq!(validator.feed(b"\x98"), Ok(()));
+        assert!(!validator.is_complete());
+        assert_eq!(validator.finish(), Err((DecodeErr::IncompleteCharacter, 2)));
+
+        // Invalid however it carries on.
+        let mut validator = Utf8Validator::new();
+        assert_eq!(validator.feed(b"abc\xE2"), Ok(()));
+        assert_eq!(
+            validator.feed(b"\x41"),
+            Err((DecodeErr::IncompleteCharacter, 3))
+        );
+        let mut validator = Utf8Validator::new();
+        assert_eq!(validator.feed(b"abc"), Ok(()));
+        assert_eq!(
+            validator.feed(b"d\xFF"),
+            Err((DecodeErr::InvalidCodeUnit, 4))
+        );
+
+        // Ends that no more bytes could make valid are errors straight away.
+        let cases: [(&[u8], usize); 5] = [
+            (b"ab\xC0", 2),
+            (b"\xF5", 0),
+            (b"\xED\xA0", 0),
+            (b"\xE0\x80", 0),
+            (b"\xF4\x90", 0),
+        ];
+        for (input, pos) in cases {
+            let mut validator = Utf8Validator::new();
+            assert_eq!(
+                validator.feed(input),
+                Err((DecodeErr::IncompleteCharacter, pos))
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_all() {
+        assert!(validate_all("ศไทย中华Việt Nam".as_bytes()).is_empty());
+        assert_eq!(
+            validate_all(b"Hello\xC0\x80 There\xE6\x83 \x80Goodbye\xF0\x9F"),
+            [
+                (DecodeErr::OverlongEncoding, 5..6),
+                (DecodeErr::UnexpectedContinuation, 6..7),
+                (DecodeErr::IncompleteCharacter, 13..15),
+                (DecodeErr::UnexpectedContinuation, 16..17),
+                (DecodeErr::IncompleteCharacter, 24..26),
+            ]
+        );
+        assert_eq!(
+            validate_all(b"\xFF\xED\xA0\x80x"),
+            [
+                (DecodeErr::InvalidCodeUnit, 0..1),
+                (DecodeErr::InvalidCodePoint, 1..2),
+                (DecodeErr::UnexpectedContinuation, 2..3),
+                (DecodeErr::UnexpectedContinuation, 3..4),
+            ]
+        );
+        // The first one is the one validate finds.
+        let input = b"ok\xE2\x82\xFF";
+        assert_eq!(
+            validate(input).err(),
+            validate_all(input)
+                .into_iter()
+                .next()
+                .map(|(err, range)| (err, range.start))
+        );
+    }
+
+    #[test]
+    fn test_validate_ascii_runs() {
+        // Errors and multi-byte characters at every offset within and around a word of ASCII.
+        for at in 0..24 {
+            let mut input = vec![b'a'; 24];
+            input.insert(at, 0xFF);
+            assert_eq!(validate(&input), Err((DecodeErr::InvalidCodeUnit, at)));
+            input[at] = 0xC3;
+            input.insert(at + 1, 0xA9);
+            assert_eq!(validate(&input), Ok(()));
+            input.pop();
+            input.push(0xE2);
+            assert_eq!(
+                validate(&input),
+                Err((DecodeErr::IncompleteCharacter, input.len() - 1))
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_utf8() {
+        let s = "hello ศไทย中华Việt Nam \u{1F600}, and some more ASCII";
+        assert_eq!(
+            decode_utf8(s.as_bytes()),
+            Ok(s.chars().map(|c| c as u32).collect())
+        );
+        assert_eq!(decode_utf8(b""), Ok(vec![]));
+        // The same errors as validate.
+        let inputs: [&[u8]; 4] = [
+            b"hello\xFF",
+            b"abcdefghij\xC0\x80",
+            b"\xE2\x82\xAC\xED\xA0\x80",
+            b"caf\xC3",
+        ];
+        for input in inputs {
+            assert_eq!(decode_utf8(input).err(), validate(input).err());
+        }
+    }
 }
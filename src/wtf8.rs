@@ -0,0 +1,142 @@
+// WTF-8 is UTF-8 that's also allowed to encode surrogates, the way it would any other three byte
+// code point, so that UTF-16 with unpaired surrogates in it, like Windows filenames and JavaScript
+// strings, can be held as bytes and turned back into exactly the same UTF-16. A high surrogate
+// followed by a low one isn't allowed, since together they have to be the four byte encoding of
+// the code point they make up, and otherwise the same text could be encoded two ways.
+// https://simonsapin.github.io/wtf-8/
+
+use crate::helpers::{decode_triple, encode_utf8_into, DecodeErr};
+use crate::utf16::encode_utf16;
+use crate::validate::decode_at;
+
+// The code point a pair of surrogates makes up, if they are a pair.
+fn surrogate_pair(high: u32, low: u32) -> Option<u32> {
+    if (0xD800..=0xDBFF).contains(&high) && (0xDC00..=0xDFFF).contains(&low) {
+        Some(0x10000 + ((high - 0xD800) << 10 | (low - 0xDC00)))
+    } else {
+        None
+    }
+}
+
+// Like decoding UTF-8, and errors are the same, except that an unpaired surrogate is a code point
+// like any other.
+pub fn decode_wtf8(bytes: &[u8]) -> Result<Vec<u32>, (DecodeErr, usize)> {
+    let mut code_points: Vec<u32> = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let (code_point, len) = match decode_at(bytes, pos) {
+            // The only three byte sequences that aren't valid code points are the surrogates.
+            Err((DecodeErr::InvalidCodePoint, _)) if bytes[pos] == 0xED => {
+                (decode_triple(bytes[pos], bytes[pos + 1], bytes[pos + 2]), 3)
+            }
+            result => result.map_err(|(err, _)| (err, pos))?,
+        };
+        if let Some(&last) = code_points.last() {
+            if surrogate_pair(last, code_point).is_some() {
+                return Err((DecodeErr::InvalidCodePoint, pos));
+            }
+        }
+        code_points.push(code_point);
+        pos += len;
+    }
+    Ok(code_points)
+}
+
+// Surrogates that make up a pair are encoded as the code point they stand for.
+pub fn encode_wtf8(code_points: &[u32]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(code_points.len());
+    let mut pos = 0;
+    while pos < code_points.len() {
+        let pair = code_points
+            .get(pos + 1)
+            .and_then(|low| surrogate_pair(code_points[pos], *low));
+        match pair {
+            Some(code_point) => {
+                encode_utf8_into(code_point, &mut out);
+                pos += 2;
+            }
+            None => {
+                encode_utf8_into(code_points[pos], &mut out);
+                pos += 1;
+            }
+        }
+    }
+    out
+}
+
+// Any UTF-16 can be converted, whether its surrogates are paired or not.
+pub fn utf16_to_wtf8(units: &[u16]) -> Vec<u8> {
+    let code_points: Vec<u32> = units.iter().map(|unit| *unit as u32).collect();
+    encode_wtf8(&code_points)
+}
+
+pub fn wtf8_to_utf16(bytes: &[u8]) -> Result<Vec<u16>, (DecodeErr, usize)> {
+    decode_wtf8(bytes).map(|code_points| encode_utf16(&code_points))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_wtf8() {
+        // UTF-8 is WTF-8 too.
+        let s = "a\u{E9}\u{20AC}\u{1F600}";
+        assert_eq!(
+            decode_wtf8(s.as_bytes()),
+            Ok(s.chars().map(|c| c as u32).collect())
+        );
+        assert_eq!(
+            decode_wtf8(b"a\xED\xA0\xBDb\xED\xB8\x80"),
+            Ok(vec![0x61, 0xD83D, 0x62, 0xDE00])
+        );
+        // A low surrogate then a high one isn't a pair.
+        assert_eq!(
+            decode_wtf8(b"\xED\xB8\x80\xED\xA0\xBD"),
+            Ok(vec![0xDE00, 0xD83D])
+        );
+        assert_eq!(
+            decode_wtf8(b"x\xED\xA0\xBD\xED\xB8\x80"),
+            Err((DecodeErr::InvalidCodePoint, 4))
+        );
+        assert_eq!(
+            decode_wtf8(b"\xED\xA0"),
+            Err((DecodeErr::IncompleteCharacter, 0))
+        );
+        assert_eq!(
+            decode_wtf8(b"\xF4\x90\x80\x80"),
+            Err((DecodeErr::InvalidCodePoint, 0))
+        );
+        assert_eq!(
+            decode_wtf8(b"\xE0\x80\x80"),
+            Err((DecodeErr::OverlongEncoding, 0))
+        );
+    }
+
+    #[test]
+    fn test_encode_wtf8() {
+        assert_eq!(encode_wtf8(&[0x61, 0xD83D]), b"a\xED\xA0\xBD");
+        assert_eq!(encode_wtf8(&[0xD83D, 0xDE00]), "\u{1F600}".as_bytes());
+        assert_eq!(
+            encode_wtf8(&[0xDE00, 0xD83D, 0xDE00, 0xD83D]),
+            b"\xED\xB8\x80\xF0\x9F\x98\x80\xED\xA0\xBD"
+        );
+    }
+
+    #[test]
+    fn test_utf16_round_trip() {
+        let inputs: [&[u16]; 4] = [
+            &[],
+            &[0x61, 0xD83D, 0xDE00],
+            &[0xDE00, 0x61, 0xD83D],
+            &[0xD800, 0xD800, 0xDFFF, 0xDFFF],
+        ];
+        for units in inputs {
+            let wtf8 = utf16_to_wtf8(units);
+            assert_eq!(wtf8_to_utf16(&wtf8), Ok(units.to_vec()));
+        }
+        // Well-formed UTF-16 gives UTF-8.
+        let units: Vec<u16> = "ศไทย\u{1F600}".encode_utf16().collect();
+        assert_eq!(utf16_to_wtf8(&units), "ศไทย\u{1F600}".as_bytes());
+    }
+}